@@ -0,0 +1,427 @@
+use std::{
+    collections::VecDeque,
+    future::Future,
+    io,
+    pin::Pin,
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError},
+        Arc, Mutex, Weak,
+    },
+    task::{Context, Poll, Waker},
+    thread,
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "hid")]
+use hidapi::HidApi;
+
+#[cfg(feature = "hid")]
+use super::discovery::open_device;
+use super::{
+    button::Button,
+    client::parse_profiles,
+    communication::{FirmwareVersion, ReplyOptions, RequestMessage, ResponseMessage},
+    frame::Reply,
+    input::InputReport,
+    transport::Transport,
+    unsorted::Profile,
+    AzeronCliError,
+};
+
+/// How long the background thread waits for input reports before checking for new requests.
+const POLL_INTERVAL_MS: i32 = 20;
+/// Input reports an `InputStream` keeps when it isn't read from, older ones are dropped.
+const MAX_QUEUED_INPUTS: usize = 256;
+
+/// An Azeron keypad that can be used from async code without blocking the executor.
+///
+/// The transport is moved to a background thread that sends requests one after another and
+/// hands input reports to every [`InputStream`] in between, so the state of the keypad can be
+/// monitored while requests are awaited. It works with any executor.
+///
+/// ```no_run
+/// use azeron_cli::azeron::async_client::AsyncAzeron;
+///
+/// # async fn example() -> Result<(), azeron_cli::AzeronCliError> {
+/// # #[cfg(feature = "hid")] {
+/// let azeron = AsyncAzeron::open(None)?;
+/// let mut inputs = azeron.inputs();
+/// println!("{} profiles", azeron.profiles().await?.len());
+/// while let Some(input) = inputs.recv().await {
+///     println!("pressed {:?}", input.pressed);
+/// }
+/// # }
+/// # Ok(())
+/// # }
+/// ```
+pub struct AsyncAzeron {
+    requests: Mutex<Sender<Job>>,
+    subscribers: Arc<Mutex<Vec<Weak<Mutex<InputQueue>>>>>,
+    options: ReplyOptions,
+}
+
+struct Job {
+    request: RequestMessage,
+    options: ReplyOptions,
+    reply: Arc<Mutex<Slot<Result<ResponseMessage, AzeronCliError>>>>,
+}
+
+#[cfg(feature = "hid")]
+impl AsyncAzeron {
+    /// Opens the only connected keypad, or the one with the given serial number or path.
+    pub fn open(selector: Option<&str>) -> Result<Self, AzeronCliError> {
+        let api = HidApi::new()?;
+
+        Ok(Self::new(open_device(&api, selector)?))
+    }
+}
+
+impl AsyncAzeron {
+    /// Moves the transport to a background thread, which stops once this is dropped.
+    pub fn new<T: Transport + Send + 'static>(transport: T) -> Self {
+        let (requests, jobs) = mpsc::channel();
+        let subscribers = Arc::new(Mutex::new(vec![]));
+
+        let thread_subscribers = subscribers.clone();
+        thread::spawn(move || {
+            let tap = InputTap { inner: transport, subscribers: thread_subscribers };
+            tap.run(jobs);
+        });
+
+        Self {
+            requests: Mutex::new(requests),
+            subscribers,
+            options: ReplyOptions::default(),
+        }
+    }
+
+    /// Changes how long to wait for replies and how often to retry.
+    pub fn with_options(mut self, options: ReplyOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn options(&self) -> &ReplyOptions {
+        &self.options
+    }
+
+    /// Sends a single message, the returned future resolves with its reply.
+    pub fn send(&self, request: RequestMessage) -> Pending<Result<ResponseMessage, AzeronCliError>> {
+        let pending = Pending::new();
+        let job = Job { request, options: self.options, reply: pending.slot.clone() };
+        if let Err(mpsc::SendError(job)) = lock(&self.requests).send(job) {
+            lock(&job.reply).resolve(Err(disconnected()));
+        }
+
+        pending
+    }
+
+    /// Subscribes to the input reports the keypad sends from now on.
+    pub fn inputs(&self) -> InputStream {
+        let queue = Arc::new(Mutex::new(InputQueue::default()));
+        lock(&self.subscribers).push(Arc::downgrade(&queue));
+
+        InputStream { queue }
+    }
+
+    async fn query(&self, request: RequestMessage) -> Result<Reply, AzeronCliError> {
+        match self.send(request).await? {
+            ResponseMessage::Reply(reply) => Ok(reply),
+            response => Err(AzeronCliError::MalformedResponse { response: response.to_string() }),
+        }
+    }
+
+    pub async fn firmware_version(&self) -> Result<FirmwareVersion, AzeronCliError> {
+        match self.query(RequestMessage::GetFirmwareVersion).await? {
+            Reply::FirmwareVersion(version) => Ok(version),
+            reply => Err(AzeronCliError::MalformedResponse { response: reply.to_string() }),
+        }
+    }
+
+    /// The profiles stored on the device.
+    pub async fn profiles(&self) -> Result<Vec<Profile>, AzeronCliError> {
+        parse_profiles(self.query(RequestMessage::GetProfiles).await?)
+    }
+
+    /// Sets a single button, `is_js_keycode` tells whether its key values are JavaScript key codes (as in profile files) or device key codes.
+    pub async fn set_button(&self, profile_id: u32, button: Button, is_js_keycode: bool) -> Result<(), AzeronCliError> {
+        self.send(RequestMessage::SetButton { profile_id, button, is_js_keycode }).await?;
+
+        Ok(())
+    }
+}
+
+/// Wraps the transport on the background thread and hands every input report it reads to the subscribers.
+struct InputTap<T> {
+    inner: T,
+    subscribers: Arc<Mutex<Vec<Weak<Mutex<InputQueue>>>>>,
+}
+
+impl<T: Transport> InputTap<T> {
+    fn run(self, jobs: Receiver<Job>) {
+        let interval = Duration::from_millis(POLL_INTERVAL_MS as u64);
+        loop {
+            let job = match jobs.try_recv() {
+                Ok(job) => job,
+                Err(TryRecvError::Disconnected) => break,
+                Err(TryRecvError::Empty) => {
+                    let started = Instant::now();
+                    let mut packet = [0u8; 64];
+                    match self.read_timeout(&mut packet, POLL_INTERVAL_MS) {
+                        Err(_) => break,
+                        Ok(read) if read > 0 => continue,
+                        // some transports return right away instead of waiting, so wait for the next request instead of spinning
+                        Ok(_) => match jobs.recv_timeout(interval.saturating_sub(started.elapsed())) {
+                            Ok(job) => job,
+                            Err(RecvTimeoutError::Timeout) => continue,
+                            Err(RecvTimeoutError::Disconnected) => break,
+                        },
+                    }
+                }
+            };
+            let result = job.request.send_message(&self, &job.options);
+            lock(&job.reply).resolve(result);
+        }
+
+        // the device is gone or the client was dropped, requests still queued can't be answered
+        for job in jobs.try_iter() {
+            lock(&job.reply).resolve(Err(disconnected()));
+        }
+        for queue in lock(&self.subscribers).drain(..).filter_map(|queue| queue.upgrade()) {
+            lock(&queue).close();
+        }
+    }
+
+    fn publish(&self, report: InputReport) {
+        let mut subscribers = lock(&self.subscribers);
+        subscribers.retain(|queue| match queue.upgrade() {
+            Some(queue) => {
+                lock(&queue).push(report.clone());
+                true
+            }
+            None => false,
+        });
+    }
+}
+
+impl<T: Transport> Transport for InputTap<T> {
+    fn write(&self, data: &[u8]) -> Result<usize, AzeronCliError> {
+        self.inner.write(data)
+    }
+
+    fn read_timeout(&self, buf: &mut [u8], timeout: i32) -> Result<usize, AzeronCliError> {
+        let read = self.inner.read_timeout(buf, timeout)?;
+        if let Some(report) = InputReport::from_report(&buf[..read]) {
+            self.publish(report);
+        }
+
+        Ok(read)
+    }
+}
+
+/// The reply to a request sent with [`AsyncAzeron::send`].
+pub struct Pending<T> {
+    slot: Arc<Mutex<Slot<T>>>,
+}
+
+struct Slot<T> {
+    value: Option<T>,
+    waker: Option<Waker>,
+}
+
+impl<T> Slot<T> {
+    fn resolve(&mut self, value: T) {
+        self.value = Some(value);
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Pending<T> {
+    fn new() -> Self {
+        Self { slot: Arc::new(Mutex::new(Slot { value: None, waker: None })) }
+    }
+}
+
+impl<T> Future for Pending<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut slot = lock(&self.slot);
+        match slot.value.take() {
+            Some(value) => Poll::Ready(value),
+            None => {
+                slot.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Input reports of an [`AsyncAzeron`], in the order they arrived.
+pub struct InputStream {
+    queue: Arc<Mutex<InputQueue>>,
+}
+
+#[derive(Default)]
+struct InputQueue {
+    reports: VecDeque<InputReport>,
+    closed: bool,
+    waker: Option<Waker>,
+}
+
+impl InputQueue {
+    fn push(&mut self, report: InputReport) {
+        if self.reports.len() == MAX_QUEUED_INPUTS {
+            self.reports.pop_front();
+        }
+        self.reports.push_back(report);
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+
+    fn close(&mut self) {
+        self.closed = true;
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl InputStream {
+    /// The next input report, or `None` once the device connection is closed.
+    pub fn recv(&mut self) -> NextInput<'_> {
+        NextInput { stream: self }
+    }
+}
+
+/// The future returned by [`InputStream::recv`].
+pub struct NextInput<'a> {
+    stream: &'a mut InputStream,
+}
+
+impl Future for NextInput<'_> {
+    type Output = Option<InputReport>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<InputReport>> {
+        let mut queue = lock(&self.stream.queue);
+        if let Some(report) = queue.reports.pop_front() {
+            return Poll::Ready(Some(report));
+        }
+        if queue.closed {
+            return Poll::Ready(None);
+        }
+        queue.waker = Some(cx.waker().clone());
+
+        Poll::Pending
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn disconnected() -> AzeronCliError {
+    AzeronCliError::Io { source: io::Error::new(io::ErrorKind::BrokenPipe, "the connection to the device was closed") }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        future::Future,
+        pin::Pin,
+        sync::{
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+            Arc,
+        },
+        task::{Context, Poll, Wake},
+        thread::{self, Thread},
+        time::Duration,
+    };
+
+    use super::AsyncAzeron;
+    use crate::azeron::{
+        communication::{RequestMessage, ResponseMessage},
+        frame::{to_azeron_message, Reply},
+        transport::{
+            fixtures::{report, trace},
+            Transport,
+        },
+        AzeronCliError,
+    };
+
+    /// A transport that never has a report and returns right away, counting how often it was read from.
+    struct Idle {
+        reads: Arc<AtomicUsize>,
+        dropped: Arc<AtomicBool>,
+    }
+
+    impl Transport for Idle {
+        fn write(&self, data: &[u8]) -> Result<usize, AzeronCliError> {
+            Ok(data.len())
+        }
+
+        fn read_timeout(&self, _buf: &mut [u8], _timeout: i32) -> Result<usize, AzeronCliError> {
+            self.reads.fetch_add(1, Ordering::Relaxed);
+            Ok(0)
+        }
+    }
+
+    impl Drop for Idle {
+        fn drop(&mut self) {
+            self.dropped.store(true, Ordering::Relaxed);
+        }
+    }
+
+    struct ThreadWaker(Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = Box::pin(future);
+        let waker = Arc::new(ThreadWaker(thread::current())).into();
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            match Pin::as_mut(&mut future).poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    #[test]
+    fn hands_out_inputs_while_awaiting_replies() {
+        let mut input = vec![0u8; 64];
+        input[4] = 1;
+        input[8] = 14;
+        input[9 + 5] = 1;
+        let azeron = AsyncAzeron::new(trace(&[("out", to_azeron_message("LEDBRT").unwrap()), ("in", input), ("in", report(b"LEDBRT_5"))]));
+        let mut inputs = azeron.inputs();
+
+        let response = block_on(azeron.send(RequestMessage::GetLEDBrightness)).unwrap();
+        let input = block_on(inputs.recv()).unwrap();
+
+        assert!(matches!(response, ResponseMessage::Reply(Reply::LEDBrightness(5))));
+        assert_eq!(input.pressed, vec![1]);
+    }
+
+    #[test]
+    fn waits_for_requests_when_reads_return_right_away() {
+        let (reads, dropped) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicBool::new(false)));
+        let azeron = AsyncAzeron::new(Idle { reads: reads.clone(), dropped: dropped.clone() });
+
+        thread::sleep(Duration::from_millis(200));
+        drop(azeron);
+        thread::sleep(Duration::from_millis(100));
+
+        // one read per poll interval of 20ms, not thousands
+        assert!(reads.load(Ordering::Relaxed) <= 20, "read {} times", reads.load(Ordering::Relaxed));
+        assert!(dropped.load(Ordering::Relaxed));
+    }
+}
@@ -1,9 +1,14 @@
 use serde::{Serialize, Deserialize};
 use serde_repr::{Serialize_repr, Deserialize_repr};
 
-use super::{unsorted::{JoystickZone, StringOrU32}, macros::AzeronMacro, statics::XINPUT_TO_READABLE};
+use super::{
+    key_manager::{from_device_meta_key, key_code_to_name},
+    macros::AzeronMacro,
+    statics::{BUTTON_HAT_NAMES, XINPUT_TO_READABLE},
+    unsorted::{JoystickZone, StringOrU32},
+};
 
-#[derive(Debug, Serialize_repr, Deserialize_repr, PartialEq)]
+#[derive(Debug, Serialize_repr, Deserialize_repr, PartialEq, Clone, Copy)]
 #[repr(u8)]
 pub enum ButtonState {
     Pressed = 0,
@@ -67,7 +72,7 @@ impl From<&ButtonType> for u8 {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Button {
     pub pins: [u8; 2],
@@ -125,51 +130,69 @@ impl Button {
         }
     }
 
+    /// A short name for what the button does, e.g. `CTRL + F` or `XBOX A`, for listings and diagrams.
     pub fn key_name(&self) -> String {
+        let value = self.key_values.first().map(|v| v.to_string()).unwrap_or_default();
         match self.button_type {
-            ButtonType::KeyboardKey => todo!(),
-            ButtonType::Switch => todo!(),
-            ButtonType::AnalogJoystick => todo!(),
-            ButtonType::AnalogJoystickWithKeys => todo!(),
-            ButtonType::JoystickButton => format!("JOY_{}", self.key_values[0]),
-            ButtonType::Disabled => "".to_string(),
-            ButtonType::AnalogJoystickWithKeysUp => todo!(),
-            ButtonType::AnalogJoystickWithKeysRight => todo!(),
-            ButtonType::AnalogJoystickWithKeysDown => todo!(),
-            ButtonType::AnalogJoystickWithKeysLeft => todo!(),
-            ButtonType::None => todo!(),
-            ButtonType::JoystickHat => todo!(),
+            ButtonType::KeyboardKey => {
+                let meta_keys = self.meta_keys.iter().filter(|m| m.to_string() != "0").map(|meta| match meta {
+                    StringOrU32::String(s) => s.to_uppercase(),
+                    StringOrU32::U32(u) => from_device_meta_key(&u.to_string()).unwrap_or_else(|_| u.to_string()),
+                });
+                let keys = self.key_values.iter().filter(|k| k.to_string() != "0").map(js_key_name);
+                meta_keys.chain(keys).collect::<Vec<_>>().join(" + ")
+            }
+            ButtonType::Switch => "SWITCH".to_string(),
+            ButtonType::AnalogJoystick => "ANALOG".to_string(),
+            ButtonType::AnalogJoystickWithKeys => "ANALOG + KEYS".to_string(),
+            ButtonType::JoystickButton => format!("JOY_{}", value),
+            ButtonType::Disabled | ButtonType::None => "".to_string(),
+            // the key pressed when the stick points in the button's direction
+            ButtonType::AnalogJoystickWithKeysUp
+            | ButtonType::AnalogJoystickWithKeysRight
+            | ButtonType::AnalogJoystickWithKeysDown
+            | ButtonType::AnalogJoystickWithKeysLeft
+            | ButtonType::AnalogJoystickAndWithKeysUp
+            | ButtonType::AnalogJoystickAndWithKeysRight
+            | ButtonType::AnalogJoystickAndWithKeysDown
+            | ButtonType::AnalogJoystickAndWithKeysLeft
+            | ButtonType::AnalogJoystickWithDriftAndKeysUp
+            | ButtonType::AnalogJoystickWithDriftAndKeysRight
+            | ButtonType::AnalogJoystickWithDriftAndKeysDown
+            | ButtonType::AnalogJoystickWithDriftAndKeysLeft => self.key_values.first().map(js_key_name).unwrap_or_default(),
+            ButtonType::JoystickHat => {
+                let name = value.parse().ok().and_then(|angle: u16| BUTTON_HAT_NAMES.get(&angle).copied());
+                format!("HAT {}", name.unwrap_or(&value))
+            }
             ButtonType::ToggleAnalog => "TGL RL ANALOG".to_string(),
             ButtonType::ToggleAnalogShort => "TGL HOLD RL ANALOG".to_string(),
-            ButtonType::MouseButton => format!("M{}", self.key_values[0]),
+            ButtonType::MouseButton => format!("M{}", value),
             ButtonType::Macro => format!("MACRO ({})", self.button_macro.steps.len()),
-            ButtonType::AnalogJoystickAndWithKeysUp => todo!(),
-            ButtonType::AnalogJoystickAndWithKeysRight => todo!(),
-            ButtonType::AnalogJoystickAndWithKeysDown => todo!(),
-            ButtonType::AnalogJoystickAndWithKeysLeft => todo!(),
-            ButtonType::XInputJoystick => todo!(),
+            ButtonType::XInputJoystick => "XBOX STICK".to_string(),
             ButtonType::XInputButton => {
-                let readable = match &self.key_values[0] {
-                    StringOrU32::String(s) => XINPUT_TO_READABLE.get(&s.parse().unwrap()).unwrap(),
-                    StringOrU32::U32(u) => XINPUT_TO_READABLE.get(u).unwrap(),
-                };
-                format!("XBOX {}", readable)
-            },
-            ButtonType::XInputTrigger => {
-                let name = match &self.key_values[0] {
-                    StringOrU32::String(s) => if s == "0" { "LT" } else { "RT" },
-                    StringOrU32::U32(u) => if *u == 0 { "LT" } else { "RT" },
-                };
-                format!("XBOX {}", name)
+                let readable = value.parse().ok().and_then(|button: u32| XINPUT_TO_READABLE.get(&button).copied());
+                format!("XBOX {}", readable.unwrap_or(&value))
             },
+            ButtonType::XInputTrigger => format!("XBOX {}", if value == "0" { "LT" } else { "RT" }),
             ButtonType::SwitchProfile => "PROFILE".to_string(),
-            ButtonType::AnalogJoystickWithDriftAndKeysUp => todo!(),
-            ButtonType::AnalogJoystickWithDriftAndKeysRight => todo!(),
-            ButtonType::AnalogJoystickWithDriftAndKeysDown => todo!(),
-            ButtonType::AnalogJoystickWithDriftAndKeysLeft => todo!(),
         }
     }
 
+    /// Whether the key value at `index` is a key code, as opposed to a mouse button, stick setting and the like.
+    pub fn holds_key_code(&self, index: usize) -> bool {
+        let holds_other_values = matches!(
+            self.button_type,
+            ButtonType::MouseButton
+                | ButtonType::JoystickButton
+                | ButtonType::XInputButton
+                | ButtonType::JoystickHat
+                | ButtonType::XInputTrigger
+                | ButtonType::SwitchProfile
+        );
+
+        !holds_other_values && !((self.is_analog_joystick() || self.button_type == ButtonType::None) && index == 3)
+    }
+
     pub fn is_analog_joystick(&self) -> bool {
         self.button_type == ButtonType::AnalogJoystick ||
         self.button_type == ButtonType::XInputJoystick ||
@@ -187,4 +210,11 @@ impl Button {
         self.button_type == ButtonType::AnalogJoystickWithDriftAndKeysDown ||
         self.button_type == ButtonType::AnalogJoystickWithDriftAndKeysLeft
     }
-}
\ No newline at end of file
+}
+
+/// The name of a key value holding a JavaScript key code, or the value itself if it has none.
+fn js_key_name(key_value: &StringOrU32) -> String {
+    let name = key_value.to_string().parse().ok().and_then(|key_code| key_code_to_name(key_code).ok());
+
+    name.map(|name| name.to_string()).unwrap_or_else(|| key_value.to_string())
+}
@@ -0,0 +1,350 @@
+#[cfg(feature = "hid")]
+use hidapi::{HidApi, HidDevice};
+
+#[cfg(feature = "hid")]
+use super::discovery::open_device;
+use super::{
+    button::{Button, ButtonType},
+    communication::{AnalogType, FirmwareVersion, ReplyOptions, RequestMessage, ResponseMessage},
+    frame::Reply,
+    input::InputReport,
+    statics::{BUTTON_IDS_TO_PINS, MAX_PROFILE_SLOT},
+    transport::Transport,
+    unsorted::Profile,
+    AzeronCliError,
+};
+
+/// The transport `Azeron` uses unless told otherwise: the HID device, or any transport without the `hid` feature.
+#[cfg(feature = "hid")]
+pub type DefaultTransport = HidDevice;
+#[cfg(not(feature = "hid"))]
+pub type DefaultTransport = Box<dyn Transport>;
+
+/// A connection to an Azeron keypad.
+///
+/// ```no_run
+/// use azeron_cli::Azeron;
+///
+/// # #[cfg(feature = "hid")] {
+/// let azeron = Azeron::open(None)?;
+/// println!("firmware {}", azeron.firmware_version()?);
+/// azeron.set_led_brightness(5)?;
+/// # }
+/// # Ok::<(), azeron_cli::AzeronCliError>(())
+/// ```
+pub struct Azeron<T: Transport = DefaultTransport> {
+    transport: T,
+    options: ReplyOptions,
+}
+
+#[cfg(feature = "hid")]
+impl Azeron<HidDevice> {
+    /// Opens the only connected keypad, or the one with the given serial number or path.
+    pub fn open(selector: Option<&str>) -> Result<Self, AzeronCliError> {
+        let api = HidApi::new()?;
+
+        Ok(Self::new(open_device(&api, selector)?))
+    }
+}
+
+impl<T: Transport> Azeron<T> {
+    /// Talks to a keypad over any transport, e.g. a `ReplayTransport` in tests.
+    pub fn new(transport: T) -> Self {
+        Self {
+            transport,
+            options: ReplyOptions::default(),
+        }
+    }
+
+    /// Changes how long to wait for replies and how often to retry.
+    pub fn with_options(mut self, options: ReplyOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn options(&self) -> &ReplyOptions {
+        &self.options
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn into_transport(self) -> T {
+        self.transport
+    }
+
+    /// Sends a single message and waits for its reply.
+    pub fn send(&self, request: &RequestMessage) -> Result<ResponseMessage, AzeronCliError> {
+        request.send_message(&self.transport, &self.options)
+    }
+
+    fn query(&self, request: RequestMessage) -> Result<Reply, AzeronCliError> {
+        match self.send(&request)? {
+            ResponseMessage::Reply(reply) => Ok(reply),
+            response => Err(AzeronCliError::MalformedResponse { response: response.to_string() }),
+        }
+    }
+
+    fn unexpected<R>(reply: Reply) -> Result<R, AzeronCliError> {
+        Err(AzeronCliError::MalformedResponse { response: reply.to_string() })
+    }
+
+    pub fn firmware_version(&self) -> Result<FirmwareVersion, AzeronCliError> {
+        match self.query(RequestMessage::GetFirmwareVersion)? {
+            Reply::FirmwareVersion(version) => Ok(version),
+            reply => Self::unexpected(reply),
+        }
+    }
+
+    /// The keypad type, e.g. `[1, 3, 0, 0]`.
+    pub fn keypad_info(&self) -> Result<Vec<u32>, AzeronCliError> {
+        match self.query(RequestMessage::GetKeypadInfo)? {
+            Reply::KeypadInfo(info) => Ok(info),
+            reply => Self::unexpected(reply),
+        }
+    }
+
+    /// The profiles stored on the device.
+    pub fn profiles(&self) -> Result<Vec<Profile>, AzeronCliError> {
+        parse_profiles(self.query(RequestMessage::GetProfiles)?)
+    }
+
+    /// Writes every button of a profile into one of the device's profile slots (0 or 1).
+    pub fn set_profile(&self, profile_id: u32, profile: &Profile) -> Result<(), AzeronCliError> {
+        for button in &profile.buttons {
+            self.set_button(profile_id, button.clone(), true)?;
+        }
+
+        Ok(())
+    }
+
+    /// Loads a software profile, which only lives on the host, into one of the device's slots.
+    ///
+    /// The slot is used as scratch space, whatever was stored in it before is overwritten.
+    pub fn load_software_profile(&self, profile: &Profile, slot: u32) -> Result<(), AzeronCliError> {
+        if !profile.is_software {
+            return Err(AzeronCliError::NotSoftwareProfile { name: profile.name.clone() });
+        }
+        if slot > MAX_PROFILE_SLOT {
+            return Err(AzeronCliError::ValueOutOfRange { name: "profile slot".to_string(), value: slot as i64, min: 0, max: MAX_PROFILE_SLOT as i64 });
+        }
+
+        self.set_profile(slot, profile)
+    }
+
+    /// Sets a single button, `is_js_keycode` tells whether its key values are JavaScript key codes (as in profile files) or device key codes.
+    pub fn set_button(&self, profile_id: u32, button: Button, is_js_keycode: bool) -> Result<(), AzeronCliError> {
+        self.send(&RequestMessage::SetButton { profile_id, button, is_js_keycode })?;
+
+        Ok(())
+    }
+
+    /// The hardware profile slot that is currently active.
+    pub fn active_profile(&self) -> Result<u32, AzeronCliError> {
+        match self.query(RequestMessage::GetActiveProfile)? {
+            Reply::ActiveProfile(profile_id) => Ok(profile_id),
+            reply => Self::unexpected(reply),
+        }
+    }
+
+    pub fn set_active_profile(&self, profile_id: u32) -> Result<(), AzeronCliError> {
+        self.send(&RequestMessage::SetActiveProfile { profile_id }).map(|_| ())
+    }
+
+    /// Turns a button of a profile slot into the button that switches between the hardware profiles.
+    pub fn bind_profile_switch(&self, profile_id: u32, button_id: u8) -> Result<(), AzeronCliError> {
+        self.set_button(profile_id, profile_switch_button(button_id)?, false)
+    }
+
+    pub fn led_state(&self) -> Result<bool, AzeronCliError> {
+        match self.query(RequestMessage::GetLEDState)? {
+            Reply::LEDState(enabled) => Ok(enabled),
+            reply => Self::unexpected(reply),
+        }
+    }
+
+    pub fn set_led_state(&self, enabled: bool) -> Result<(), AzeronCliError> {
+        self.send(&RequestMessage::SetLEDState { enabled }).map(|_| ())
+    }
+
+    pub fn led_brightness(&self) -> Result<u8, AzeronCliError> {
+        match self.query(RequestMessage::GetLEDBrightness)? {
+            Reply::LEDBrightness(brightness) => Ok(brightness),
+            reply => Self::unexpected(reply),
+        }
+    }
+
+    pub fn set_led_brightness(&self, brightness: u8) -> Result<(), AzeronCliError> {
+        self.send(&RequestMessage::SetLEDBrightness { brightness }).map(|_| ())
+    }
+
+    pub fn analog_type(&self) -> Result<AnalogType, AzeronCliError> {
+        match self.query(RequestMessage::GetAnalogType)? {
+            Reply::AnalogType(analog_type) => Ok(analog_type),
+            reply => Self::unexpected(reply),
+        }
+    }
+
+    pub fn set_analog_type(&self, analog_type: AnalogType) -> Result<(), AzeronCliError> {
+        self.send(&RequestMessage::SetAnalogType { analog_type }).map(|_| ())
+    }
+
+    pub fn button_throttle(&self) -> Result<u8, AzeronCliError> {
+        match self.query(RequestMessage::GetButtonThrottle)? {
+            Reply::ButtonThrottle(throttle) => Ok(throttle),
+            reply => Self::unexpected(reply),
+        }
+    }
+
+    pub fn set_button_throttle(&self, throttle: u8) -> Result<(), AzeronCliError> {
+        self.send(&RequestMessage::SetButtonThrottle { throttle }).map(|_| ())
+    }
+
+    pub fn throttle_type(&self) -> Result<u8, AzeronCliError> {
+        match self.query(RequestMessage::GetThrottleType)? {
+            Reply::ThrottleType(throttle_type) => Ok(throttle_type),
+            reply => Self::unexpected(reply),
+        }
+    }
+
+    pub fn set_throttle_type(&self, throttle_type: u8) -> Result<(), AzeronCliError> {
+        self.send(&RequestMessage::SetThrottleType { throttle_type }).map(|_| ())
+    }
+
+    /// The hardware analog offset as `(x, y)`.
+    pub fn hardware_analog_offset(&self) -> Result<(i16, i16), AzeronCliError> {
+        match self.query(RequestMessage::GetHardwareAnalogOffset)? {
+            Reply::HardwareAnalogOffset { x, y } => Ok((x, y)),
+            reply => Self::unexpected(reply),
+        }
+    }
+
+    pub fn set_hardware_analog_offset(&self, x: i16, y: i16) -> Result<(), AzeronCliError> {
+        self.send(&RequestMessage::SetHardwareAnalogOffset { x, y }).map(|_| ())
+    }
+
+    /// The hardware deadzones as `(lower, upper)`.
+    pub fn hardware_deadzones(&self) -> Result<(u16, u16), AzeronCliError> {
+        let lower = match self.query(RequestMessage::GetHardwareLowerDeadzone)? {
+            Reply::HardwareLowerDeadzone(deadzone) => deadzone,
+            reply => return Self::unexpected(reply),
+        };
+        let upper = match self.query(RequestMessage::GetHardwareUpperDeadzone)? {
+            Reply::HardwareUpperDeadzone(deadzone) => deadzone,
+            reply => return Self::unexpected(reply),
+        };
+
+        Ok((lower, upper))
+    }
+
+    pub fn set_hardware_deadzones(&self, lower: u16, upper: u16) -> Result<(), AzeronCliError> {
+        self.send(&RequestMessage::SetHardwareLowerDeadzone { deadzone: lower })?;
+        self.send(&RequestMessage::SetHardwareUpperDeadzone { deadzone: upper })?;
+
+        Ok(())
+    }
+
+    /// Reads input reports and hands them to `on_input` until it returns `false`.
+    ///
+    /// Replies to commands that arrive in between are ignored.
+    pub fn monitor_input(&self, mut on_input: impl FnMut(InputReport) -> bool) -> Result<(), AzeronCliError> {
+        let timeout = self.options.timeout.as_millis() as i32;
+        loop {
+            let mut packet = [0u8; 64];
+            let read = self.transport.read_timeout(&mut packet, timeout)?;
+            if let Some(report) = InputReport::from_report(&packet[..read]) {
+                if !on_input(report) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// Reads the profiles out of a `GET_PROFILES` reply, a JSON list in the format of profile files.
+///
+/// Fails with `MalformedResponse` if the reply is anything else.
+pub fn parse_profiles(reply: Reply) -> Result<Vec<Profile>, AzeronCliError> {
+    match reply {
+        Reply::Other(profiles) => serde_json::from_str(&profiles).map_err(|_| AzeronCliError::MalformedResponse { response: profiles }),
+        reply => Err(AzeronCliError::MalformedResponse { response: reply.to_string() }),
+    }
+}
+
+/// A button that switches between the hardware profiles when pressed.
+pub fn profile_switch_button(button_id: u8) -> Result<Button, AzeronCliError> {
+    let pins = *BUTTON_IDS_TO_PINS.get(&button_id).ok_or(AzeronCliError::InvalidButtonId { button_id })?;
+
+    Ok(Button::new(button_id, ButtonType::SwitchProfile, pins, vec![], vec![]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Azeron;
+    use crate::azeron::{
+        frame::to_azeron_message,
+        transport::fixtures::{report, trace},
+        unsorted::Profile,
+        AzeronCliError,
+    };
+
+    #[test]
+    fn reads_profiles_spanning_several_reports() {
+        let profiles = serde_json::to_string(&[Profile::new(0, "Left"), Profile::new(1, "Right")]).unwrap();
+        let frame = to_azeron_message(&profiles).unwrap();
+        let mut entries = vec![("out", to_azeron_message("GET_PROFILES").unwrap())];
+        entries.extend(frame[1..].chunks(64).map(|chunk| ("in", report(chunk))));
+        let azeron = Azeron::new(trace(&entries));
+
+        let profiles = azeron.profiles().unwrap();
+
+        assert_eq!(profiles.iter().map(|p| (p.id, p.name.as_str())).collect::<Vec<_>>(), [(0, "Left"), (1, "Right")]);
+        assert!(azeron.transport().is_finished());
+    }
+
+    #[test]
+    fn reads_the_status() {
+        let azeron = Azeron::new(trace(&[
+            ("out", to_azeron_message("GET_FW_VERSION").unwrap()),
+            ("in", report(b"FWV_60_0")),
+            ("out", to_azeron_message("GET_PROFILE").unwrap()),
+            ("in", report(b"PROFILE_1")),
+        ]));
+
+        assert_eq!(azeron.firmware_version().unwrap().to_string(), "60.0");
+        assert_eq!(azeron.active_profile().unwrap(), 1);
+        assert!(azeron.transport().is_finished());
+    }
+
+    #[test]
+    fn refuses_unconfirmed_setters() {
+        // nothing may be written, so an empty trace fails on any write
+        let azeron = Azeron::new(trace(&[]));
+
+        let switched = azeron.set_active_profile(1);
+        let dimmed = azeron.set_led_brightness(5);
+
+        assert!(matches!(switched, Err(AzeronCliError::UnconfirmedCommand { command }) if command == "SET_PROFILE|1"));
+        assert!(matches!(dimmed, Err(AzeronCliError::UnconfirmedCommand { command }) if command == "LEDBRT|5"));
+        // values out of range are still reported as such
+        assert!(matches!(azeron.set_active_profile(2), Err(AzeronCliError::ValueOutOfRange { .. })));
+    }
+
+    #[test]
+    fn binds_the_profile_switch() {
+        let azeron = Azeron::new(trace(&[("out", to_azeron_message("B0|22|24|38|255|0|0|0|0|0|0|0|0").unwrap()), ("in", report(b"BOK_22"))]));
+
+        azeron.bind_profile_switch(0, 22).unwrap();
+
+        assert!(azeron.transport().is_finished());
+        assert!(matches!(azeron.bind_profile_switch(0, 39), Err(AzeronCliError::InvalidButtonId { button_id: 39 })));
+    }
+
+    #[test]
+    fn rejects_profiles_that_arent_json() {
+        let azeron = Azeron::new(trace(&[("out", to_azeron_message("GET_PROFILES").unwrap()), ("in", report(b"PROFILES_0_1"))]));
+
+        assert!(matches!(azeron.profiles(), Err(AzeronCliError::MalformedResponse { .. })));
+    }
+}
@@ -1,9 +1,30 @@
-use std::{num::Wrapping, fmt::Display};
+use std::{fmt::Display, time::{Duration, Instant}};
 
-use hidapi::HidDevice;
+use serde::{Deserialize, Serialize};
 
-use super::{unsorted::StringOrU32, button::{ButtonType, Button}, statics::JAVASCRIPT_TO_DEVICE_KEY, key_manager::meta_key_to_device_code};
+use super::{
+    unsorted::StringOrU32,
+    button::Button,
+    statics::{JAVASCRIPT_TO_DEVICE_KEY, MIN_FIRMWARE_VERSION, MAX_LED_BRIGHTNESS, MAX_BUTTON_THROTTLE, MAX_THROTTLE_TYPE, MAX_HARDWARE_DEADZONE, MAX_HARDWARE_ANALOG_OFFSET, MAX_PROFILE_SLOT, MAX_KEY_VALUES, MAX_META_KEYS},
+    key_manager::meta_key_to_device_code,
+    frame::{to_azeron_message, FrameDecoder, Reply},
+    transport::Transport,
+    AzeronCliError,
+};
 
+/// The shape the analog stick's range is mapped to.
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[repr(u8)]
+pub enum AnalogType {
+    Square = 0,
+    Circle = 1,
+}
+
+/// A message to the device.
+///
+/// Serializes to JSON like `"GetLEDBrightness"` or `{"SetLEDBrightness":{"brightness":5}}`, which is what the daemon socket speaks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum RequestMessage {
     /// Sends `Hi` to the Azeron device.
     KeepAlive,
@@ -79,10 +100,74 @@ pub enum RequestMessage {
     ///
     /// Example: `HWUPRDZ_298`
     GetHardwareUpperDeadzone,
+    /// Sends `GET_PROFILE` to the Azeron device.
+    ///
+    /// Returns the active hardware profile slot as a string. Like the setters below, this hasn't been checked
+    /// against a capture of the official app yet.
+    ///
+    /// Example: `PROFILE_0`
+    GetActiveProfile,
     /// Sends `GET_RIGHT_ANALOG` to the Azeron device.
     ///
     /// Returns the right analog setting as a string.
     GetRightAnalogSetting,
+    // The setters below follow the getters above and use `|` between arguments like `SetButton` does.
+    // Their replies match the getters, but the commands haven't been checked against a capture of the official app,
+    // so they aren't sent until they are (see `is_unconfirmed`). `Custom` sends them anyway.
+    /// Sends `SET_LEDS|<state>` to the Azeron device.
+    ///
+    /// <state> is `1` to turn the LEDs on and `0` to turn them off. Returns the new LED state as a string.
+    ///
+    /// Example: `LEDS_OFF`
+    SetLEDState { enabled: bool },
+    /// Sends `LEDBRT|<brightness>` to the Azeron device.
+    ///
+    /// <brightness> ranges from 0 to `MAX_LED_BRIGHTNESS`. Returns the new LED brightness as a string.
+    ///
+    /// Example: `LEDBRT_3`
+    SetLEDBrightness { brightness: u8 },
+    /// Sends `SET_ANALOG_TYPE|<type>` to the Azeron device.
+    ///
+    /// <type> is `0` for square and `1` for circle. Returns the new analog type as a string.
+    ///
+    /// Example: `ANALOG_CIRCLE`
+    SetAnalogType { analog_type: AnalogType },
+    /// Sends `BTNTHT|<throttle>` to the Azeron device.
+    ///
+    /// <throttle> ranges from 0 to `MAX_BUTTON_THROTTLE`. Returns the new button throttle as a string.
+    ///
+    /// Example: `BTNTHT_15`
+    SetButtonThrottle { throttle: u8 },
+    /// Sends `SET_THROTTLE_TYPE|<type>` to the Azeron device.
+    ///
+    /// <type> ranges from 0 to `MAX_THROTTLE_TYPE`. Returns the new throttle type as a string.
+    ///
+    /// Example: `THROTTLE_TYPE_0`
+    SetThrottleType { throttle_type: u8 },
+    /// Sends `HWANLGOFST|<x>|<y>` to the Azeron device.
+    ///
+    /// Both offsets range from `-MAX_HARDWARE_ANALOG_OFFSET` to `MAX_HARDWARE_ANALOG_OFFSET`. Returns the new hardware analog offset as a string.
+    ///
+    /// Example: `HWANLGOFST_-5_-8`
+    SetHardwareAnalogOffset { x: i16, y: i16 },
+    /// Sends `HWLWRDZ|<deadzone>` to the Azeron device.
+    ///
+    /// <deadzone> ranges from 0 to `MAX_HARDWARE_DEADZONE`. Returns the new lower hardware deadzone as a string.
+    ///
+    /// Example: `HWLWRDZ_10`
+    SetHardwareLowerDeadzone { deadzone: u16 },
+    /// Sends `HWUPRDZ|<deadzone>` to the Azeron device.
+    ///
+    /// <deadzone> ranges from 0 to `MAX_HARDWARE_DEADZONE`. Returns the new upper hardware deadzone as a string.
+    ///
+    /// Example: `HWUPRDZ_298`
+    SetHardwareUpperDeadzone { deadzone: u16 },
+    /// Sends `SET_PROFILE|<profile_id>` to the Azeron device.
+    ///
+    /// <profile_id> is the hardware profile slot to switch to (0 or 1). Returns the new active profile as a string.
+    ///
+    /// Example: `PROFILE_1`
+    SetActiveProfile { profile_id: u32 },
     SetButton {
         profile_id: u32,
         button: Button,
@@ -90,44 +175,87 @@ pub enum RequestMessage {
     },
     /// Sends a custom command to the Azeron device.
     ///
-    /// This may or may not return a response, so only use this if you know what you're doing.
+    /// The command is framed like every other message. This may or may not return a response,
+    /// so only use this if you know what you're doing.
     Custom(String),
 }
 
+#[derive(Debug, Serialize, Deserialize)]
 pub enum ResponseMessage {
     KeepAlive(Vec<u8>),
     SetButtonResponse(bool),
+    /// A decoded reply from the device, e.g. `LEDBRT_5`.
+    Reply(Reply),
     None,
 }
 
 impl RequestMessage {
-    pub fn send_message(&self, device: &HidDevice) -> Result<ResponseMessage, ()> {
+    /// Checks that the values of a setter message are within the ranges the device accepts.
+    pub fn validate(&self) -> Result<(), AzeronCliError> {
         match self {
-            RequestMessage::KeepAlive => {
-                let msg = to_azeron_message("Hi").unwrap();
-                device.write(&msg).unwrap();
-                let mut packet = [0u8; 64];
-                device.read(&mut packet).unwrap();
-                println!("{:?}", packet);
-                let mut slice = packet[9..9 + packet[8] as usize].to_vec();
-                let message_type = packet[4];
-                handle_hid_payload(message_type, &mut slice);
-
-                Ok(ResponseMessage::KeepAlive(slice))
+            RequestMessage::SetLEDBrightness { brightness } => check_range("LED brightness", *brightness as i64, 0, MAX_LED_BRIGHTNESS as i64),
+            RequestMessage::SetButtonThrottle { throttle } => check_range("button throttle", *throttle as i64, 0, MAX_BUTTON_THROTTLE as i64),
+            RequestMessage::SetThrottleType { throttle_type } => check_range("throttle type", *throttle_type as i64, 0, MAX_THROTTLE_TYPE as i64),
+            RequestMessage::SetHardwareAnalogOffset { x, y } => {
+                check_range("analog offset x", *x as i64, -(MAX_HARDWARE_ANALOG_OFFSET as i64), MAX_HARDWARE_ANALOG_OFFSET as i64)?;
+                check_range("analog offset y", *y as i64, -(MAX_HARDWARE_ANALOG_OFFSET as i64), MAX_HARDWARE_ANALOG_OFFSET as i64)
+            }
+            RequestMessage::SetHardwareLowerDeadzone { deadzone } => check_range("lower deadzone", *deadzone as i64, 0, MAX_HARDWARE_DEADZONE as i64),
+            RequestMessage::SetHardwareUpperDeadzone { deadzone } => check_range("upper deadzone", *deadzone as i64, 0, MAX_HARDWARE_DEADZONE as i64),
+            RequestMessage::SetActiveProfile { profile_id } => check_range("profile slot", *profile_id as i64, 0, MAX_PROFILE_SLOT as i64),
+            // a line break would end the frame early and the rest would be read as garbage by the firmware
+            RequestMessage::Custom(msg) if msg.is_empty() || msg.contains('\n') || !msg.is_ascii() => {
+                Err(AzeronCliError::InvalidMessage { message: msg.to_string() })
             }
-            RequestMessage::GetFirmwareVersion => todo!(),
-            RequestMessage::GetLEDState => todo!(),
-            RequestMessage::GetLEDBrightness => todo!(),
-            RequestMessage::GetAnalogType => todo!(),
-            RequestMessage::GetProfiles => todo!(),
-            RequestMessage::GetSwitchState { id } => todo!(),
-            RequestMessage::GetKeypadInfo => todo!(),
-            RequestMessage::GetButtonThrottle => todo!(),
-            RequestMessage::GetThrottleType => todo!(),
-            RequestMessage::GetHardwareAnalogOffset => todo!(),
-            RequestMessage::GetHardwareLowerDeadzone => todo!(),
-            RequestMessage::GetHardwareUpperDeadzone => todo!(),
-            RequestMessage::GetRightAnalogSetting => todo!(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether the command of this message is a guess that hasn't been confirmed against a capture of the official app.
+    ///
+    /// These messages are refused by `send_message`, their frames can still be built to compare them with a capture.
+    pub fn is_unconfirmed(&self) -> bool {
+        matches!(
+            self,
+            RequestMessage::SetLEDState { .. }
+                | RequestMessage::SetLEDBrightness { .. }
+                | RequestMessage::SetAnalogType { .. }
+                | RequestMessage::SetButtonThrottle { .. }
+                | RequestMessage::SetThrottleType { .. }
+                | RequestMessage::SetHardwareAnalogOffset { .. }
+                | RequestMessage::SetHardwareLowerDeadzone { .. }
+                | RequestMessage::SetHardwareUpperDeadzone { .. }
+                | RequestMessage::SetActiveProfile { .. }
+        )
+    }
+
+    /// Builds the command string that is sent to the device for this message, without the framing.
+    pub fn command(&self) -> Result<String, AzeronCliError> {
+        let command = match self {
+            RequestMessage::KeepAlive => "Hi".to_string(),
+            RequestMessage::GetFirmwareVersion => "GET_FW_VERSION".to_string(),
+            RequestMessage::GetLEDState => "GET_LEDS".to_string(),
+            RequestMessage::GetLEDBrightness => "LEDBRT".to_string(),
+            RequestMessage::GetAnalogType => "GET_ANALOG_TYPE".to_string(),
+            RequestMessage::GetProfiles => "GET_PROFILES".to_string(),
+            RequestMessage::GetSwitchState { id } => format!("ST|{}", id),
+            RequestMessage::GetKeypadInfo => "GET_FW_TYPE".to_string(),
+            RequestMessage::GetButtonThrottle => "BTNTHT".to_string(),
+            RequestMessage::GetThrottleType => "GET_THROTTLE_TYPE".to_string(),
+            RequestMessage::GetHardwareAnalogOffset => "HWANLGOFST".to_string(),
+            RequestMessage::GetHardwareLowerDeadzone => "HWLWRDZ".to_string(),
+            RequestMessage::GetHardwareUpperDeadzone => "HWUPRDZ".to_string(),
+            RequestMessage::GetActiveProfile => "GET_PROFILE".to_string(),
+            RequestMessage::GetRightAnalogSetting => "GET_RIGHT_ANALOG".to_string(),
+            RequestMessage::SetLEDState { enabled } => format!("SET_LEDS|{}", u8::from(*enabled)),
+            RequestMessage::SetLEDBrightness { brightness } => format!("LEDBRT|{}", brightness),
+            RequestMessage::SetAnalogType { analog_type } => format!("SET_ANALOG_TYPE|{}", *analog_type as u8),
+            RequestMessage::SetButtonThrottle { throttle } => format!("BTNTHT|{}", throttle),
+            RequestMessage::SetThrottleType { throttle_type } => format!("SET_THROTTLE_TYPE|{}", throttle_type),
+            RequestMessage::SetHardwareAnalogOffset { x, y } => format!("HWANLGOFST|{}|{}", x, y),
+            RequestMessage::SetHardwareLowerDeadzone { deadzone } => format!("HWLWRDZ|{}", deadzone),
+            RequestMessage::SetHardwareUpperDeadzone { deadzone } => format!("HWUPRDZ|{}", deadzone),
+            RequestMessage::SetActiveProfile { profile_id } => format!("SET_PROFILE|{}", profile_id),
             RequestMessage::SetButton {
                 profile_id,
                 button,
@@ -135,50 +263,166 @@ impl RequestMessage {
             } => {
                 let mut keys = button.key_values.iter().enumerate().map(|(index, key_value)| {
                     let key_value = match key_value {
-                        StringOrU32::String(s) => s.parse().unwrap(),
+                        StringOrU32::String(s) => s.parse().map_err(|_| AzeronCliError::UndefinedKeyCode { key_name: s.to_string() })?,
                         StringOrU32::U32(u) => *u,
                     };
                     
-                    if button.button_type == ButtonType::MouseButton || button.button_type == ButtonType::JoystickButton || 
-                    button.button_type == ButtonType::XInputButton || button.button_type == ButtonType::JoystickHat ||
-                    button.button_type == ButtonType::XInputTrigger || button.button_type == ButtonType::SwitchProfile ||
-                    ((button.is_analog_joystick() || button.button_type == ButtonType::None) && index == 3) {
-                        key_value.to_string()
+                    if !button.holds_key_code(index) {
+                        Ok(key_value.to_string())
                     } else if *is_js_keycode {
-                        JAVASCRIPT_TO_DEVICE_KEY.get(&key_value).unwrap().to_string()
+                        JAVASCRIPT_TO_DEVICE_KEY
+                            .get(&key_value)
+                            .map(|device_key| device_key.to_string())
+                            .ok_or(AzeronCliError::KeyNotSupported { key_name: key_value.to_string() })
                     } else {
-                        key_value.to_string()
+                        Ok(key_value.to_string())
                     }
                 })
-                .collect::<Vec<String>>();
-                keys.resize(4, "0".to_string());
+                .collect::<Result<Vec<String>, AzeronCliError>>()?;
+                keys.resize(MAX_KEY_VALUES, "0".to_string());
                 let keys = keys.join("|");
                 let mut meta_keys = button.meta_keys.iter().map(|key| {
                     let key = match key {
-                        StringOrU32::String(s) => meta_key_to_device_code(s),
+                        StringOrU32::String(s) => meta_key_to_device_code(s)?,
                         StringOrU32::U32(u) => *u,
                     };
                     
-                    key.to_string()
+                    Ok(key.to_string())
                 })
-                .collect::<Vec<String>>();
-                meta_keys.resize(3, "0".to_string());
+                .collect::<Result<Vec<String>, AzeronCliError>>()?;
+                meta_keys.resize(MAX_META_KEYS, "0".to_string());
                 let meta_keys = meta_keys.join("|");
-                let msg_str = format!("B{}|{}|{}|{}|{}|{}|{}|0", profile_id, button.id, u8::from(&button.button_type), button.pins[0], button.pins[1], keys, meta_keys);
-                let msg = to_azeron_message(&msg_str).unwrap();
-                device.write(&msg).unwrap();
-                let mut packet = [0u8; 64];
-                device.read(&mut packet).unwrap();
-                let device_response = String::from_utf8(packet.to_vec());
-                if let Err(e) = device_response {
-                    println!("Error: {:?}", e);
-                    return Err(());
+                format!("B{}|{}|{}|{}|{}|{}|{}|0", profile_id, button.id, u8::from(&button.button_type), button.pins[0], button.pins[1], keys, meta_keys)
+            },
+            RequestMessage::Custom(msg) => msg.to_string(),
+        };
+
+        Ok(command)
+    }
+
+    /// Validates this message and builds the exact frame that is written to the device.
+    pub fn frame(&self) -> Result<Vec<u8>, AzeronCliError> {
+        self.validate()?;
+        to_azeron_message(&self.command()?)
+    }
+
+    /// The prefix of the reply the device sends back for this message, if it is known.
+    pub fn reply_prefix(&self) -> Option<String> {
+        let prefix = match self {
+            RequestMessage::GetFirmwareVersion => "FWV_".to_string(),
+            RequestMessage::GetLEDState | RequestMessage::SetLEDState { .. } => "LEDS_".to_string(),
+            RequestMessage::GetLEDBrightness | RequestMessage::SetLEDBrightness { .. } => "LEDBRT_".to_string(),
+            RequestMessage::GetAnalogType | RequestMessage::SetAnalogType { .. } => "ANALOG_".to_string(),
+            RequestMessage::GetSwitchState { .. } => "ST".to_string(),
+            RequestMessage::GetKeypadInfo => "FWT_".to_string(),
+            RequestMessage::GetButtonThrottle | RequestMessage::SetButtonThrottle { .. } => "BTNTHT_".to_string(),
+            RequestMessage::GetThrottleType | RequestMessage::SetThrottleType { .. } => "THROTTLE_TYPE_".to_string(),
+            RequestMessage::GetHardwareAnalogOffset | RequestMessage::SetHardwareAnalogOffset { .. } => "HWANLGOFST_".to_string(),
+            RequestMessage::GetHardwareLowerDeadzone | RequestMessage::SetHardwareLowerDeadzone { .. } => "HWLWRDZ_".to_string(),
+            RequestMessage::GetHardwareUpperDeadzone | RequestMessage::SetHardwareUpperDeadzone { .. } => "HWUPRDZ_".to_string(),
+            RequestMessage::GetActiveProfile | RequestMessage::SetActiveProfile { .. } => "PROFILE_".to_string(),
+            RequestMessage::SetButton { button, .. } => format!("BOK_{}", button.id),
+            RequestMessage::KeepAlive
+            | RequestMessage::GetProfiles
+            | RequestMessage::GetRightAnalogSetting
+            | RequestMessage::Custom(_) => return None,
+        };
+
+        Some(prefix)
+    }
+
+    pub fn send_message<T: Transport + ?Sized>(&self, device: &T, options: &ReplyOptions) -> Result<ResponseMessage, AzeronCliError> {
+        let msg = self.frame()?;
+        if self.is_unconfirmed() {
+            return Err(AzeronCliError::UnconfirmedCommand { command: self.command()? });
+        }
+        let prefix = self.reply_prefix();
+        let mut unmatched_reply = None;
+
+        for _ in 0..=options.retries {
+            device.write(&msg)?;
+            let deadline = Instant::now() + options.timeout;
+            let mut decoder = FrameDecoder::new();
+            let mut packet = [0u8; 64];
+
+            while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+                packet.fill(0);
+                if device.read_timeout(&mut packet, remaining.as_millis() as i32)? == 0 {
+                    break;
                 }
-                let device_response = device_response.unwrap();
 
-                Ok(ResponseMessage::SetButtonResponse(device_response.starts_with(format!("BOK_{}", button.id).as_str())))
-            },
-            RequestMessage::Custom(msg) => todo!(),
+                if let RequestMessage::KeepAlive = self {
+                    return keep_alive_response(&packet);
+                }
+
+                // input reports can arrive at any time, even between the reports of a frame
+                let report = match reply_report(&packet, decoder.is_pending()) {
+                    Some(report) => report,
+                    None => continue,
+                };
+
+                // a report that can't be decoded isn't the reply either, it may still follow
+                let reply = match decoder.push(report) {
+                    Ok(Some(reply)) => reply,
+                    Ok(None) | Err(_) => continue,
+                };
+                match &prefix {
+                    Some(prefix) if !reply.starts_with(prefix.as_str()) => {
+                        unmatched_reply = Some(reply);
+                    }
+                    _ => {
+                        return match self {
+                            RequestMessage::SetButton { .. } => Ok(ResponseMessage::SetButtonResponse(true)),
+                            _ => Ok(ResponseMessage::Reply(Reply::parse(&reply)?)),
+                        };
+                    }
+                }
+            }
+        }
+
+        match unmatched_reply {
+            Some(response) => Err(AzeronCliError::Nak { response }),
+            None => Err(AzeronCliError::Timeout),
+        }
+    }
+
+    /// Sends this message once and collects every report that arrives, until `count` reports were read or `timeout` passed.
+    ///
+    /// A `count` of 0 collects reports until the timeout. Nothing is skipped, input reports are collected as well.
+    pub fn collect_reports<T: Transport + ?Sized>(&self, device: &T, count: usize, timeout: Duration) -> Result<Vec<Vec<u8>>, AzeronCliError> {
+        device.write(&self.frame()?)?;
+
+        let deadline = Instant::now() + timeout;
+        let mut reports = vec![];
+        while count == 0 || reports.len() < count {
+            let remaining = match deadline.checked_duration_since(Instant::now()) {
+                Some(remaining) => remaining,
+                None => break,
+            };
+            let mut packet = [0u8; 64];
+            let read = device.read_timeout(&mut packet, remaining.as_millis() as i32)?;
+            if read == 0 {
+                break;
+            }
+            reports.push(packet[..read].to_vec());
+        }
+
+        Ok(reports)
+    }
+}
+
+/// How long to wait for a reply and how often to resend a request that got none.
+#[derive(Debug, Clone, Copy)]
+pub struct ReplyOptions {
+    pub timeout: Duration,
+    pub retries: u32,
+}
+
+impl Default for ReplyOptions {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_millis(1000),
+            retries: 2,
         }
     }
 }
@@ -189,72 +433,138 @@ impl Display for ResponseMessage {
             ResponseMessage::KeepAlive(msg) => write!(f, "{:?}", msg),
             ResponseMessage::None => write!(f, ""),
             ResponseMessage::SetButtonResponse(success) => write!(f, "{}", success),
+            ResponseMessage::Reply(reply) => write!(f, "{}", reply),
         }
     }
 }
 
-fn is_set(byte: u8, bytes: &[u8]) -> bool {
-    let index = 5 - (byte - 1) / 8;
-    0 != (bytes[index as usize] & 1 << ((byte - 1) % 8))
+/// A firmware version as reported by `GET_FW_VERSION`, e.g. `FWV_60_0`.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Deserialize)]
+pub struct FirmwareVersion {
+    pub major: u32,
+    pub minor: u32,
 }
 
-fn to_azeron_message(message: &str) -> Result<Vec<u8>, ()> {
-    // ensure message contains only ascii characters
-    if message.chars().any(|c| !c.is_ascii()) {
-        return Err(());
+impl FirmwareVersion {
+    pub fn parse(reply: &str) -> Result<Self, AzeronCliError> {
+        let malformed = || AzeronCliError::MalformedResponse { response: reply.to_string() };
+        let mut parts = reply.strip_prefix("FWV_").ok_or_else(malformed)?.split('_');
+        let major = parts.next().and_then(|p| p.parse().ok()).ok_or_else(malformed)?;
+        let minor = parts.next().and_then(|p| p.parse().ok()).unwrap_or(0);
+
+        Ok(Self { major, minor })
+    }
+
+    /// Fails with `UnsupportedFirmware` if this version is older than the oldest one this tool was written against.
+    pub fn ensure_supported(&self) -> Result<(), AzeronCliError> {
+        if *self < MIN_FIRMWARE_VERSION {
+            return Err(AzeronCliError::UnsupportedFirmware { version: self.to_string() });
+        }
+
+        Ok(())
     }
-    let bytes = message.as_bytes();
-    // get length of bytes as ascii characters
-    let length = bytes.len();
-    // convert length to ascii characters
-    let length_str = length.to_string();
-    let length_bytes = length_str.as_bytes();
-
-    // message is ^length_bytes~message\n
-    let mut message_bytes = vec![0, b'^'];
-    message_bytes.extend_from_slice(length_bytes);
-    message_bytes.push(b'~');
-    message_bytes.extend_from_slice(bytes);
-    message_bytes.push(b'\n');
-
-    Ok(message_bytes)
 }
 
-#[allow(arithmetic_overflow)]
-fn handle_hid_payload(message_type: u8, payload: &mut [u8]) {
-    if message_type != 1 {
-        return;
+impl Display for FirmwareVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
     }
+}
 
-    #[derive(Debug)]
-    struct Internal {
-        x: Wrapping<u8>,
-        y: Wrapping<u8>,
+/// Asks the device for its firmware version.
+pub fn firmware_version<T: Transport + ?Sized>(device: &T, options: &ReplyOptions) -> Result<FirmwareVersion, AzeronCliError> {
+    match RequestMessage::GetFirmwareVersion.send_message(device, options)? {
+        ResponseMessage::Reply(Reply::FirmwareVersion(version)) => Ok(version),
+        response => Err(AzeronCliError::MalformedResponse { response: response.to_string() }),
     }
-    let i = Internal {
-        x: Wrapping(payload[11]) << 8 | Wrapping(payload[10]),
-        y: Wrapping(payload[13]) << 8 | Wrapping(payload[12]),
-    };
-    let o = Internal {
-        x: Wrapping(payload[7]) << 8 | Wrapping(payload[6]),
-        y: Wrapping(payload[9]) << 8 | Wrapping(payload[8]),
+}
+
+fn check_range(name: &str, value: i64, min: i64, max: i64) -> Result<(), AzeronCliError> {
+    if value < min || value > max {
+        return Err(AzeronCliError::ValueOutOfRange { name: name.to_string(), value, min, max });
+    }
+
+    Ok(())
+}
+
+fn keep_alive_response(packet: &[u8]) -> Result<ResponseMessage, AzeronCliError> {
+    let length = packet[8] as usize;
+    if 9 + length > packet.len() {
+        return Err(AzeronCliError::MalformedResponse { response: format!("{:?}", packet) });
+    }
+    Ok(ResponseMessage::KeepAlive(packet[9..9 + length].to_vec()))
+}
+
+/// Returns the part of a report that can belong to a reply, or `None` for input reports.
+///
+/// Replies are printable ASCII padded with zeros, either a `^<len>~<payload>\n` frame (optionally behind its report ID)
+/// or bare text like `BOK_15`. While a frame is pending only text that continues it is accepted.
+fn reply_report(packet: &[u8], pending: bool) -> Option<&[u8]> {
+    let end = packet.iter().rposition(|b| *b != 0).map_or(0, |i| i + 1);
+    let report = match &packet[..end] {
+        [0, b'^', ..] if !pending => &packet[1..end],
+        report => report,
     };
-    println!("{:?}", i);
-    println!("{:?}", o);
-
-    // for i in 0..38 {
-    //     let j = i + 1;
-    //     if !((24..=27).contains(&j) || (32..=35).contains(&j)) {
-    //         let is_set = if is_set(j, payload) { 1 } else { 0 };
-    //         for profile in profiles.iter_mut() {
-    //             if let Some(button) = profile.buttons.get_mut(j as usize) {
-    //                 button.state = is_set;
-    //             }
-    //         }
-    //     }
-    // }
-
-    // for profile in profiles.iter_mut() {
-
-    // }
-}
\ No newline at end of file
+
+    let is_text = !report.is_empty() && report.iter().all(|b| b.is_ascii_graphic() || *b == b' ' || *b == b'\n');
+    let starts_reply = matches!(report.first(), Some(b) if *b == b'^' || b.is_ascii_uppercase());
+    (is_text && (pending || starts_reply)).then_some(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AnalogType, RequestMessage};
+    use crate::azeron::{button::{Button, ButtonType}, unsorted::StringOrU32};
+    use crate::azeron::statics::{MAX_BUTTON_THROTTLE, MAX_HARDWARE_ANALOG_OFFSET, MAX_HARDWARE_DEADZONE, MAX_LED_BRIGHTNESS, MAX_THROTTLE_TYPE};
+
+    fn frame(request: RequestMessage) -> String {
+        String::from_utf8(request.frame().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn builds_set_button_frames() {
+        let request = RequestMessage::SetButton {
+            profile_id: 1,
+            button: Button::new(10, ButtonType::KeyboardKey, [0, 255], vec![61459.into()], vec![StringOrU32::String("CTRL".to_string())]),
+            is_js_keycode: false,
+        };
+
+        assert_eq!(frame(request), "\0^37~B1|10|1|0|255|61459|0|0|0|57345|0|0|0\n");
+    }
+
+    #[test]
+    fn builds_setting_frames() {
+        assert_eq!(frame(RequestMessage::SetLEDState { enabled: false }), "\0^10~SET_LEDS|0\n");
+        assert_eq!(frame(RequestMessage::SetLEDState { enabled: true }), "\0^10~SET_LEDS|1\n");
+        assert_eq!(frame(RequestMessage::SetLEDBrightness { brightness: 5 }), "\0^8~LEDBRT|5\n");
+        assert_eq!(frame(RequestMessage::SetButtonThrottle { throttle: 10 }), "\0^9~BTNTHT|10\n");
+        assert_eq!(frame(RequestMessage::SetThrottleType { throttle_type: 1 }), "\0^19~SET_THROTTLE_TYPE|1\n");
+        assert_eq!(frame(RequestMessage::SetAnalogType { analog_type: AnalogType::Circle }), "\0^17~SET_ANALOG_TYPE|1\n");
+        assert_eq!(frame(RequestMessage::SetHardwareAnalogOffset { x: -5, y: -8 }), "\0^16~HWANLGOFST|-5|-8\n");
+        assert_eq!(frame(RequestMessage::SetHardwareLowerDeadzone { deadzone: 0 }), "\0^9~HWLWRDZ|0\n");
+        assert_eq!(frame(RequestMessage::SetHardwareUpperDeadzone { deadzone: 298 }), "\0^11~HWUPRDZ|298\n");
+        assert_eq!(frame(RequestMessage::SetActiveProfile { profile_id: 1 }), "\0^13~SET_PROFILE|1\n");
+    }
+
+    #[test]
+    fn rejects_out_of_range_settings() {
+        assert!(RequestMessage::SetLEDBrightness { brightness: 11 }.frame().is_err());
+        assert!(RequestMessage::SetButtonThrottle { throttle: MAX_BUTTON_THROTTLE + 1 }.frame().is_err());
+        assert!(RequestMessage::SetThrottleType { throttle_type: 3 }.frame().is_err());
+        assert!(RequestMessage::SetHardwareAnalogOffset { x: 0, y: -513 }.frame().is_err());
+        assert!(RequestMessage::SetHardwareLowerDeadzone { deadzone: 513 }.frame().is_err());
+        assert!(RequestMessage::SetHardwareUpperDeadzone { deadzone: 513 }.frame().is_err());
+        assert!(RequestMessage::SetActiveProfile { profile_id: 2 }.frame().is_err());
+        assert!(RequestMessage::Custom("GET_LEDS\nLEDBRT".to_string()).frame().is_err());
+    }
+
+    #[test]
+    fn accepts_settings_at_their_limits() {
+        assert!(RequestMessage::SetLEDBrightness { brightness: MAX_LED_BRIGHTNESS }.frame().is_ok());
+        assert!(RequestMessage::SetButtonThrottle { throttle: MAX_BUTTON_THROTTLE }.frame().is_ok());
+        assert!(RequestMessage::SetThrottleType { throttle_type: MAX_THROTTLE_TYPE }.frame().is_ok());
+        let offset = MAX_HARDWARE_ANALOG_OFFSET;
+        assert!(RequestMessage::SetHardwareAnalogOffset { x: -offset, y: offset }.frame().is_ok());
+        assert!(RequestMessage::SetHardwareUpperDeadzone { deadzone: MAX_HARDWARE_DEADZONE }.frame().is_ok());
+    }
+}
@@ -0,0 +1,412 @@
+use std::{
+    env, fs,
+    io::{self, BufRead, BufReader, Write},
+    os::unix::{
+        fs::PermissionsExt,
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    client::Azeron,
+    communication::{ReplyOptions, RequestMessage, ResponseMessage},
+    focus::{AutoSwitcher, FocusSource, SharedFocus, SwitchTarget},
+    store::ProfileStore,
+    transport::Transport,
+    unsorted::Profile,
+    AzeronCliError,
+};
+
+/// How often the daemon checks for new connections and whether it should stop.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The socket the daemon listens on unless told otherwise, `$XDG_RUNTIME_DIR/azeron-cli.sock`.
+///
+/// Falls back to a per-user socket in the temp directory if there is no runtime directory.
+pub fn default_socket_path() -> PathBuf {
+    match env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => PathBuf::from(dir).join("azeron-cli.sock"),
+        None => env::temp_dir().join(format!("azeron-cli-{}.sock", env::var("USER").unwrap_or_default())),
+    }
+}
+
+/// A request sent over the daemon socket, one JSON object per line.
+///
+/// Protocol messages are passed on to the device as they are, e.g. `{"SetLEDBrightness":{"brightness":5}}`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum DaemonRequest {
+    Message(RequestMessage),
+    Command(DaemonCommand),
+}
+
+/// Requests the daemon handles itself instead of passing them on.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum DaemonCommand {
+    /// Loads a software profile into the daemon's scratch slot, `{"LoadSoftwareProfile":{"profile":{...}}}`.
+    LoadSoftwareProfile { profile: Profile },
+    /// Asks which software profile the daemon loaded, `"Status"`.
+    Status,
+    /// Reports the focused application for automatic switching, `{"SetFocus":{"application":"eldenring.exe"}}`.
+    SetFocus { application: Option<String> },
+    /// Sends a raw command and collects the reports the device sends back, `{"Raw":{"command":"GET_FW_TYPE","replies":3,"timeout_ms":2000}}`.
+    ///
+    /// Waits for `replies` reports (0 waits for the whole timeout), like `azeron-cli raw` does.
+    Raw { command: String, replies: usize, timeout_ms: u64 },
+}
+
+/// What the daemon knows beyond what the device itself reports.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DaemonStatus {
+    pub software_slot: u32,
+    /// The software profile that was loaded last, if any.
+    pub software_profile: Option<Profile>,
+}
+
+/// The reply to a request sent over the daemon socket, one JSON object per line.
+///
+/// `{"ok":{"Reply":{"LEDBrightness":5}}}`, `{"status":{...}}` or `{"error":{"message":"...","exit_code":5}}`
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DaemonResponse {
+    Ok(ResponseMessage),
+    Status(DaemonStatus),
+    /// The reports a raw command got back, as they were read.
+    Reports(Vec<Vec<u8>>),
+    Error { message: String, exit_code: i32 },
+}
+
+impl From<Result<DaemonResponse, AzeronCliError>> for DaemonResponse {
+    fn from(result: Result<DaemonResponse, AzeronCliError>) -> Self {
+        match result {
+            Ok(response) => response,
+            Err(e) => DaemonResponse::Error { message: e.to_string(), exit_code: e.exit_code() },
+        }
+    }
+}
+
+pub struct DaemonOptions {
+    pub reply: ReplyOptions,
+    /// How often `Hi` is sent to keep the device in configuration mode.
+    pub keep_alive: Duration,
+    /// The slot software profiles are loaded into.
+    pub software_slot: u32,
+    /// Updated with the applications reported by `SetFocus`, poll it in `auto_switch` to switch on them.
+    pub focus: SharedFocus,
+    pub auto_switch: Option<AutoSwitch>,
+}
+
+/// Switches profiles based on the applications a focus source reports.
+pub struct AutoSwitch {
+    pub source: Box<dyn FocusSource>,
+    pub switcher: AutoSwitcher,
+    /// Where profiles named in the rules are loaded from.
+    pub store: ProfileStore,
+    /// How often the source is polled.
+    pub interval: Duration,
+}
+
+/// Binds the daemon socket, replacing a stale one left behind by a daemon that didn't shut down cleanly.
+///
+/// Only the current user can connect to it.
+pub fn bind(path: &Path) -> Result<UnixListener, AzeronCliError> {
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            return Err(AzeronCliError::DaemonRunning { path: path.display().to_string() });
+        }
+        fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+
+    Ok(listener)
+}
+
+/// Keeps the device open and answers requests on the socket until `stop` is set or the device fails.
+///
+/// Every connection is handled on its own thread, requests are sent to the device one at a time.
+pub fn serve<T: Transport + Send + 'static>(transport: T, listener: UnixListener, options: DaemonOptions, stop: &AtomicBool) -> Result<(), AzeronCliError> {
+    let state = Arc::new(DaemonState {
+        device: Mutex::new(Azeron::new(transport).with_options(options.reply)),
+        software_slot: options.software_slot,
+        software_profile: Mutex::new(None),
+        focus: options.focus,
+    });
+    listener.set_nonblocking(true)?;
+    let mut last_keep_alive = Instant::now();
+    let mut auto_switch = options.auto_switch;
+    let mut last_focus_poll = Instant::now();
+
+    while !stop.load(Ordering::Relaxed) {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                stream.set_nonblocking(false)?;
+                let state = state.clone();
+                thread::spawn(move || handle_connection(stream, &state));
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+            Err(e) => return Err(e.into()),
+        }
+
+        if last_keep_alive.elapsed() >= options.keep_alive {
+            let device = state.device.lock().unwrap_or_else(|e| e.into_inner());
+            match device.send(&RequestMessage::KeepAlive) {
+                // the device doesn't always answer, that's fine as long as it's still there
+                Ok(_) | Err(AzeronCliError::Timeout) => {}
+                Err(e) => return Err(e),
+            }
+            last_keep_alive = Instant::now();
+        }
+
+        if let Some(auto_switch) = auto_switch.as_mut().filter(|a| last_focus_poll.elapsed() >= a.interval) {
+            // a rule pointing at a missing profile shouldn't take the daemon down
+            let switched = auto_switch
+                .switcher
+                .poll(&mut *auto_switch.source)
+                .and_then(|target| target.map(|target| state.switch_to(&target, &auto_switch.store).map(|_| target)).transpose());
+            match switched {
+                Ok(Some(target)) => {
+                    println!("Switched to {}.", target);
+                    auto_switch.switcher.switched(target);
+                }
+                Ok(None) => {}
+                Err(e) => eprintln!("error: automatic switching failed: {}", e),
+            }
+            last_focus_poll = Instant::now();
+        }
+    }
+
+    Ok(())
+}
+
+struct DaemonState<T: Transport> {
+    device: Mutex<Azeron<T>>,
+    software_slot: u32,
+    software_profile: Mutex<Option<Profile>>,
+    focus: SharedFocus,
+}
+
+impl<T: Transport> DaemonState<T> {
+    fn switch_to(&self, target: &SwitchTarget, store: &ProfileStore) -> Result<(), AzeronCliError> {
+        let device = self.device.lock().unwrap_or_else(|e| e.into_inner());
+        match target {
+            SwitchTarget::Slot(slot) => device.set_active_profile(*slot),
+            SwitchTarget::Profile(name) => {
+                self.load(&device, store.load(name)?)?;
+                device.set_active_profile(self.software_slot)
+            }
+        }
+    }
+
+    /// Loads a software profile into the scratch slot and remembers it.
+    fn load(&self, device: &Azeron<T>, profile: Profile) -> Result<(), AzeronCliError> {
+        let mut loaded = self.software_profile.lock().unwrap_or_else(|e| e.into_inner());
+        // forget what was loaded in case loading fails halfway
+        *loaded = None;
+        device.load_software_profile(&profile, self.software_slot)?;
+        *loaded = Some(profile);
+
+        Ok(())
+    }
+
+    fn handle(&self, request: DaemonRequest) -> Result<DaemonResponse, AzeronCliError> {
+        let device = self.device.lock().unwrap_or_else(|e| e.into_inner());
+        let response = match request {
+            DaemonRequest::Message(request) => {
+                request.validate()?;
+                if matches!(request, RequestMessage::SetButton { profile_id, .. } if profile_id == self.software_slot) {
+                    // the scratch slot no longer holds the profile loaded last
+                    *self.software_profile.lock().unwrap_or_else(|e| e.into_inner()) = None;
+                }
+                DaemonResponse::Ok(device.send(&request)?)
+            }
+            DaemonRequest::Command(DaemonCommand::LoadSoftwareProfile { profile }) => {
+                self.load(&device, profile)?;
+                DaemonResponse::Ok(ResponseMessage::None)
+            }
+            DaemonRequest::Command(DaemonCommand::Status) => DaemonResponse::Status(DaemonStatus {
+                software_slot: self.software_slot,
+                software_profile: self.software_profile.lock().unwrap_or_else(|e| e.into_inner()).clone(),
+            }),
+            DaemonRequest::Command(DaemonCommand::SetFocus { application }) => {
+                self.focus.set(application);
+                DaemonResponse::Ok(ResponseMessage::None)
+            }
+            DaemonRequest::Command(DaemonCommand::Raw { command, replies, timeout_ms }) => {
+                let reports = RequestMessage::Custom(command).collect_reports(device.transport(), replies, Duration::from_millis(timeout_ms))?;
+                DaemonResponse::Reports(reports)
+            }
+        };
+
+        Ok(response)
+    }
+}
+
+fn handle_connection<T: Transport>(stream: UnixStream, state: &DaemonState<T>) {
+    let mut writer = &stream;
+    for line in BufReader::new(&stream).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => return,
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = DaemonResponse::from(serde_json::from_str(&line).map_err(AzeronCliError::from).and_then(|request| state.handle(request)));
+        let sent = serde_json::to_string(&response).map_err(io::Error::from).and_then(|json| writeln!(writer, "{}", json));
+        if sent.is_err() {
+            return;
+        }
+    }
+}
+
+/// A connection to a running daemon.
+pub struct DaemonClient {
+    stream: UnixStream,
+    reader: Mutex<BufReader<UnixStream>>,
+}
+
+impl DaemonClient {
+    pub fn connect(path: &Path) -> Result<Self, AzeronCliError> {
+        let stream = UnixStream::connect(path)?;
+        let reader = Mutex::new(BufReader::new(stream.try_clone()?));
+
+        Ok(Self { stream, reader })
+    }
+
+    /// Sends a single message through the daemon and waits for its reply.
+    ///
+    /// Errors the daemon ran into are returned as `AzeronCliError::Daemon`, with the exit code it would have used.
+    pub fn send(&self, request: &RequestMessage) -> Result<ResponseMessage, AzeronCliError> {
+        match self.request(&DaemonRequest::Message(request.clone()))? {
+            DaemonResponse::Ok(response) => Ok(response),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// Has the daemon load a software profile into its scratch slot.
+    pub fn load_software_profile(&self, profile: &Profile) -> Result<(), AzeronCliError> {
+        self.request(&DaemonRequest::Command(DaemonCommand::LoadSoftwareProfile { profile: profile.clone() }))?;
+
+        Ok(())
+    }
+
+    /// Has the daemon send a raw command and returns the reports it got back, see `RequestMessage::collect_reports`.
+    pub fn collect_reports(&self, command: &str, count: usize, timeout: Duration) -> Result<Vec<Vec<u8>>, AzeronCliError> {
+        let raw = DaemonCommand::Raw { command: command.to_string(), replies: count, timeout_ms: timeout.as_millis() as u64 };
+        match self.request(&DaemonRequest::Command(raw))? {
+            DaemonResponse::Reports(reports) => Ok(reports),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// Reports the focused application, for a daemon that switches profiles based on it.
+    pub fn set_focus(&self, application: Option<String>) -> Result<(), AzeronCliError> {
+        self.request(&DaemonRequest::Command(DaemonCommand::SetFocus { application }))?;
+
+        Ok(())
+    }
+
+    /// Asks the daemon which software profile it loaded, marking it active if its slot is `active_profile`.
+    pub fn status(&self, active_profile: u32) -> Result<DaemonStatus, AzeronCliError> {
+        match self.request(&DaemonRequest::Command(DaemonCommand::Status))? {
+            DaemonResponse::Status(mut status) => {
+                if let Some(profile) = &mut status.software_profile {
+                    profile.is_active = status.software_slot == active_profile;
+                }
+                Ok(status)
+            }
+            response => Err(unexpected(response)),
+        }
+    }
+
+    fn request(&self, request: &DaemonRequest) -> Result<DaemonResponse, AzeronCliError> {
+        let mut reader = self.reader.lock().unwrap_or_else(|e| e.into_inner());
+        writeln!(&self.stream, "{}", serde_json::to_string(request)?)?;
+
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the daemon closed the connection").into());
+        }
+        match serde_json::from_str(&line)? {
+            DaemonResponse::Error { message, exit_code } => Err(AzeronCliError::Daemon { message, code: exit_code }),
+            response => Ok(response),
+        }
+    }
+}
+
+fn unexpected(response: DaemonResponse) -> AzeronCliError {
+    AzeronCliError::MalformedResponse { response: format!("{:?}", response) }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{atomic::{AtomicBool, Ordering}, Arc},
+        thread,
+        time::Duration,
+    };
+
+    use super::{bind, serve, DaemonClient, DaemonCommand, DaemonOptions, DaemonRequest};
+    use crate::azeron::{
+        communication::{ReplyOptions, RequestMessage, ResponseMessage},
+        frame::{to_azeron_message, Reply},
+        transport::fixtures::{report, trace},
+        AzeronCliError,
+    };
+
+    #[test]
+    fn answers_requests_over_the_socket() {
+        let replay = trace(&[
+            ("out", to_azeron_message("LEDBRT").unwrap()),
+            ("in", report(b"LEDBRT_5")),
+            ("out", to_azeron_message("GET_FW_TYPE").unwrap()),
+            ("in", report(b"FWT_1_3_0_0")),
+            ("in", report(b"FWV_60_0")),
+        ]);
+        let path = std::env::temp_dir().join(format!("azeron-cli-daemon-{}.sock", std::process::id()));
+        let listener = bind(&path).unwrap();
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let daemon_stop = stop.clone();
+        let daemon = thread::spawn(move || {
+            let options = DaemonOptions { reply: ReplyOptions::default(), keep_alive: Duration::from_secs(3600), software_slot: 1, focus: Default::default(), auto_switch: None };
+            serve(replay, listener, options, &daemon_stop)
+        });
+
+        let client = DaemonClient::connect(&path).unwrap();
+        let response = client.send(&RequestMessage::GetLEDBrightness).unwrap();
+        let rejected = client.send(&RequestMessage::SetLEDBrightness { brightness: 11 });
+        let reports = client.collect_reports("GET_FW_TYPE", 2, Duration::from_secs(1)).unwrap();
+        stop.store(true, Ordering::Relaxed);
+        daemon.join().unwrap().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(response, ResponseMessage::Reply(Reply::LEDBrightness(5))));
+        assert!(matches!(rejected, Err(AzeronCliError::Daemon { code: 2, .. })));
+        assert_eq!(reports, [report(b"FWT_1_3_0_0"), report(b"FWV_60_0")]);
+    }
+
+
+    #[test]
+    fn tells_messages_and_commands_apart() {
+        let message: DaemonRequest = serde_json::from_str(r#"{"SetLEDBrightness":{"brightness":5}}"#).unwrap();
+        let command: DaemonRequest = serde_json::from_str(
+            r#"{"LoadSoftwareProfile":{"profile":{"buttons":[],"isFavourite":false,"isSoftware":true,"hash":"","id":7,"name":"Racing"}}}"#,
+        )
+        .unwrap();
+
+        assert!(matches!(message, DaemonRequest::Message(RequestMessage::SetLEDBrightness { brightness: 5 })));
+        assert!(matches!(command, DaemonRequest::Command(DaemonCommand::LoadSoftwareProfile { profile }) if profile.name == "Racing"));
+    }
+}
@@ -0,0 +1,116 @@
+use super::{button::Button, unsorted::Profile};
+
+/// The buttons as laid out in `docs/assets/ids.png`, as `(button ID, column, row)` in cells.
+const LAYOUT: &[(u8, usize, usize)] = &[
+    (28, 6, 0),
+    (4, 1, 1), (8, 2, 1), (12, 3, 1), (17, 4, 1), (29, 5, 1), (22, 6, 1), (31, 7, 1),
+    (3, 1, 2), (7, 2, 2), (11, 3, 2), (16, 4, 2), (30, 6, 2),
+    (36, 0, 3), (2, 1, 3), (6, 2, 3), (10, 3, 3), (15, 4, 3), (19, 5, 3),
+    (1, 1, 4), (5, 2, 4), (9, 3, 4), (14, 4, 4),
+    (37, 1, 5), (38, 2, 5), (13, 3, 5), (18, 4, 5), (23, 6, 5), (20, 7, 5),
+];
+/// The stick takes up two by two cells, starting here.
+const STICK_CELL: (usize, usize) = (6, 3);
+/// The buttons for the directions of the stick, up, right, down and left.
+const STICK_DIRECTIONS: [u8; 4] = [24, 25, 26, 27];
+const COLUMNS: usize = 8;
+const ROWS: usize = 6;
+/// The size of a cell including its border, cells are a space apart.
+const CELL_WIDTH: usize = 9;
+const CELL_HEIGHT: usize = 3;
+
+/// Draws the keypad with the label or key name of every button in the profile.
+///
+/// Buttons that aren't part of the drawing, like the switch, are listed below it.
+pub fn render_diagram(profile: &Profile) -> String {
+    let mut canvas = vec![vec![' '; COLUMNS * (CELL_WIDTH + 1)]; ROWS * CELL_HEIGHT];
+    for (button_id, column, row) in LAYOUT {
+        let label = label_of(profile, *button_id);
+        draw_box(&mut canvas, (*column, *row), (1, 1), &button_id.to_string(), &[label]);
+    }
+
+    let direction = |index: usize| label_of(profile, STICK_DIRECTIONS[index]);
+    let inner_width = 2 * CELL_WIDTH - 1;
+    let sides = format!("← {}", truncate(&direction(3), inner_width / 2 - 2));
+    let stick = [
+        format!("{:^width$}", format!("↑ {}", direction(0)), width = inner_width),
+        format!("{}{:>width$}", sides, format!("{} →", direction(1)), width = inner_width - sides.chars().count()),
+        format!("{:^width$}", format!("↓ {}", direction(2)), width = inner_width),
+    ];
+    let title = format!("{}-{}", STICK_DIRECTIONS[0], STICK_DIRECTIONS[3]);
+    draw_box(&mut canvas, STICK_CELL, (2, 2), &title, &stick);
+
+    let mut diagram = canvas.iter().map(|line| line.iter().collect::<String>().trim_end().to_string()).collect::<Vec<_>>().join("\n");
+    let drawn = |id: u8| LAYOUT.iter().any(|(button_id, _, _)| *button_id == id) || STICK_DIRECTIONS.contains(&id);
+    for button in profile.buttons.iter().filter(|b| !drawn(b.id)) {
+        diagram.push_str(&format!("\n{:>2}: {}", button.id, button_label(button)));
+    }
+
+    diagram
+}
+
+/// The label of a button in the profile, empty if it isn't bound.
+fn label_of(profile: &Profile, button_id: u8) -> String {
+    profile.buttons.iter().find(|b| b.id == button_id).map(button_label).unwrap_or_default()
+}
+
+/// The user label of a button, or the keys it is bound to.
+fn button_label(button: &Button) -> String {
+    match button.user_label.trim() {
+        "" => button.key_name(),
+        label => label.to_string(),
+    }
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    text.chars().take(width).collect()
+}
+
+/// Draws a box over `columns` by `rows` cells, with the title in its top border and a line of text per row inside it.
+fn draw_box(canvas: &mut [Vec<char>], (column, row): (usize, usize), (columns, rows): (usize, usize), title: &str, lines: &[String]) {
+    let (x, y) = (column * (CELL_WIDTH + 1), row * CELL_HEIGHT);
+    let width = columns * (CELL_WIDTH + 1) - 1;
+    let height = rows * CELL_HEIGHT;
+
+    let mut put = |x: usize, y: usize, c: char| canvas[y][x] = c;
+    for dx in 1..width - 1 {
+        put(x + dx, y, '─');
+        put(x + dx, y + height - 1, '─');
+    }
+    for dy in 1..height - 1 {
+        put(x, y + dy, '│');
+        put(x + width - 1, y + dy, '│');
+    }
+    put(x, y, '┌');
+    put(x + width - 1, y, '┐');
+    put(x, y + height - 1, '└');
+    put(x + width - 1, y + height - 1, '┘');
+
+    for (i, c) in truncate(title, width - 2).chars().enumerate() {
+        put(x + 1 + i, y, c);
+    }
+    for (dy, line) in lines.iter().take(height - 2).enumerate() {
+        for (i, c) in truncate(line, width - 2).chars().enumerate() {
+            put(x + 1 + i, y + 1 + dy, c);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render_diagram;
+    use crate::azeron::templates::Template;
+
+    #[test]
+    fn draws_every_button() {
+        let profile = Template::find("fps").unwrap().instantiate("fps").unwrap();
+        let diagram = render_diagram(&profile);
+        let lines = diagram.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines[0], format!("{:60}┌28─────┐", ""));
+        assert_eq!(lines[4], format!("{:10}│Esc    │ │1      │ │2      │ │3      │ │5      │ │Space  │ │Y      │", ""));
+        assert_eq!(lines[10], format!("│ALT    │ │SHIFT  │ │G      │ │F      │ │T      │ │H      │ │{:^17}│", "↑ W"));
+        assert!(lines[11].ends_with(&format!("│← A{:>14}│", "D →")));
+        assert_eq!(lines[18..], ["21: PROFILE", "32: W", "33: D", "34: S", "35: A"]);
+    }
+}
@@ -0,0 +1,71 @@
+use hidapi::{DeviceInfo, HidApi, HidDevice};
+
+use super::{
+    communication::{firmware_version, FirmwareVersion, ReplyOptions, RequestMessage, ResponseMessage},
+    frame::Reply,
+    statics::{AZERON_INTERFACE, AZERON_PRODUCT_ID, AZERON_VENDOR_ID},
+    AzeronCliError,
+};
+
+/// A connected Azeron keypad, as shown by `azeron-cli devices`.
+#[derive(Debug)]
+pub struct AzeronDevice {
+    pub path: String,
+    pub serial_number: Option<String>,
+    pub model: Option<String>,
+    /// Firmware version and keypad type, if the device could be opened to ask for them.
+    pub firmware: Result<(FirmwareVersion, Vec<u32>), AzeronCliError>,
+}
+
+/// Lists the configuration interfaces of all connected Azeron keypads.
+pub fn azeron_devices(api: &HidApi) -> impl Iterator<Item = &DeviceInfo> {
+    api.device_list().filter(|d| {
+        d.vendor_id() == AZERON_VENDOR_ID && d.product_id() == AZERON_PRODUCT_ID && d.interface_number() == AZERON_INTERFACE
+    })
+}
+
+/// Lists all connected Azeron keypads and asks each of them for its firmware.
+pub fn list_devices(api: &HidApi, options: &ReplyOptions) -> Vec<AzeronDevice> {
+    azeron_devices(api)
+        .map(|info| AzeronDevice {
+            path: info.path().to_string_lossy().to_string(),
+            serial_number: info.serial_number().map(|s| s.to_string()),
+            model: info.product_string().map(|s| s.to_string()),
+            firmware: open_info(api, info).and_then(|device| {
+                let version = firmware_version(&device, options)?;
+                match RequestMessage::GetKeypadInfo.send_message(&device, options)? {
+                    ResponseMessage::Reply(Reply::KeypadInfo(keypad_type)) => Ok((version, keypad_type)),
+                    response => Err(AzeronCliError::MalformedResponse { response: response.to_string() }),
+                }
+            }),
+        })
+        .collect()
+}
+
+/// Opens the Azeron keypad with the given serial number or path.
+///
+/// Without a selector, the only connected keypad is opened. If there are several, one has to be selected.
+pub fn open_device(api: &HidApi, selector: Option<&str>) -> Result<HidDevice, AzeronCliError> {
+    let mut candidates = azeron_devices(api)
+        .filter(|d| match selector {
+            Some(selector) => d.serial_number() == Some(selector) || d.path().to_string_lossy() == selector,
+            None => true,
+        })
+        .collect::<Vec<_>>();
+
+    match candidates.len() {
+        0 => Err(AzeronCliError::DeviceNotFound),
+        1 => open_info(api, candidates.remove(0)),
+        count => Err(AzeronCliError::MultipleDevices { count }),
+    }
+}
+
+fn open_info(api: &HidApi, info: &DeviceInfo) -> Result<HidDevice, AzeronCliError> {
+    info.open_device(api).map_err(|e| {
+        let path = info.path().to_string_lossy().to_string();
+        match std::fs::OpenOptions::new().read(true).write(true).open(&path) {
+            Err(io) if io.kind() == std::io::ErrorKind::PermissionDenied => AzeronCliError::PermissionDenied { path },
+            _ => AzeronCliError::Hid { source: e },
+        }
+    })
+}
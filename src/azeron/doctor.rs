@@ -0,0 +1,128 @@
+use std::{
+    fs,
+    io,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+};
+
+use super::statics::{AZERON_PRODUCT_ID, AZERON_VENDOR_ID};
+
+pub const UDEV_RULE_PATH: &str = "/etc/udev/rules.d/50-azeron.rules";
+
+/// Gives the logged in user access to the keypad's hidraw nodes, without needing root.
+pub const UDEV_RULE: &str = r#"# Azeron keypads, installed by azeron-cli
+SUBSYSTEM=="hidraw", ATTRS{idVendor}=="16d0", ATTRS{idProduct}=="10bc", MODE="0660", TAG+="uaccess"
+"#;
+
+/// A `/dev/hidraw*` node that belongs to an Azeron keypad.
+#[derive(Debug)]
+pub struct HidrawNode {
+    pub path: PathBuf,
+    /// The USB interface the node belongs to, only interface 4 speaks the configuration protocol.
+    pub interface: Option<i32>,
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    /// Whether the node could be opened for reading and writing by the current user.
+    pub access: Result<(), io::ErrorKind>,
+}
+
+/// Finds the hidraw nodes of all Azeron keypads by looking through `sys_class` (usually `/sys/class/hidraw`).
+///
+/// The device nodes themselves are looked up in `dev` (usually `/dev`).
+pub fn hidraw_nodes(sys_class: &Path, dev: &Path) -> io::Result<Vec<HidrawNode>> {
+    let hid_id = format!("HID_ID=0003:{:08X}:{:08X}", AZERON_VENDOR_ID, AZERON_PRODUCT_ID);
+    let mut nodes = vec![];
+
+    let entries = match fs::read_dir(sys_class) {
+        Ok(entries) => entries,
+        // no hidraw devices at all, or the hidraw module isn't loaded
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(nodes),
+        Err(e) => return Err(e),
+    };
+    for entry in entries {
+        let entry = entry?;
+        let uevent = match fs::read_to_string(entry.path().join("device/uevent")) {
+            Ok(uevent) => uevent,
+            Err(_) => continue,
+        };
+        if !uevent.lines().any(|line| line.eq_ignore_ascii_case(&hid_id)) {
+            continue;
+        }
+
+        let path = dev.join(entry.file_name());
+        let (mode, uid, gid) = match fs::metadata(&path) {
+            Ok(metadata) => (metadata.mode(), metadata.uid(), metadata.gid()),
+            Err(_) => (0, 0, 0),
+        };
+        let access = fs::OpenOptions::new().read(true).write(true).open(&path).map(|_| ()).map_err(|e| e.kind());
+
+        nodes.push(HidrawNode {
+            path,
+            interface: usb_interface(&entry.path().join("device")),
+            mode,
+            uid,
+            gid,
+            access,
+        });
+    }
+    nodes.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(nodes)
+}
+
+/// The HID device lives below its USB interface, which is named like `1-2:1.4` for interface 4.
+fn usb_interface(device: &Path) -> Option<i32> {
+    let device = fs::canonicalize(device).ok()?;
+    let interface = device.parent()?.file_name()?.to_string_lossy().to_string();
+
+    interface.rsplit_once('.')?.1.parse().ok()
+}
+
+/// Formats a mode like `ls -l` does, e.g. `rw-rw----`.
+pub fn format_mode(mode: u32) -> String {
+    ["r", "w", "x"]
+        .iter()
+        .cycle()
+        .take(9)
+        .enumerate()
+        .map(|(i, flag)| if mode & (0o400 >> i) != 0 { *flag } else { "-" })
+        .collect()
+}
+
+pub fn install_udev_rule(path: &Path) -> io::Result<()> {
+    fs::write(path, UDEV_RULE)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{format_mode, hidraw_nodes};
+
+    #[test]
+    fn finds_azeron_hidraw_nodes() {
+        let root = std::env::temp_dir().join(format!("azeron-cli-doctor-{}", std::process::id()));
+        let sys_class = root.join("sys");
+        let dev = root.join("dev");
+        for (node, hid_id) in [("hidraw0", "0003:0000046D:0000C52B"), ("hidraw3", "0003:000016D0:000010BC")] {
+            fs::create_dir_all(sys_class.join(node).join("device")).unwrap();
+            fs::write(sys_class.join(node).join("device/uevent"), format!("DRIVER=hid-generic\nHID_ID={}\n", hid_id)).unwrap();
+        }
+        fs::create_dir_all(&dev).unwrap();
+        fs::write(dev.join("hidraw3"), "").unwrap();
+
+        let nodes = hidraw_nodes(&sys_class, &dev).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].path, dev.join("hidraw3"));
+        assert!(nodes[0].access.is_ok());
+    }
+
+    #[test]
+    fn formats_modes_like_ls() {
+        assert_eq!(format_mode(0o660), "rw-rw----");
+        assert_eq!(format_mode(0o600), "rw-------");
+    }
+}
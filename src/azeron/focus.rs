@@ -0,0 +1,310 @@
+use std::{
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use super::AzeronCliError;
+
+/// Tells the daemon which applications are in use, so it can switch to the profile configured for them.
+///
+/// Implementations are polled periodically. Application names are the file names of executables, like `eldenring.exe`.
+pub trait FocusSource: Send {
+    /// The applications currently in use, the most relevant first.
+    fn applications(&mut self) -> Result<Vec<String>, AzeronCliError>;
+}
+
+/// Every running process, read from `/proc`. Works without a display, but can't tell which game is in front.
+pub struct ProcSource {
+    proc_dir: PathBuf,
+}
+
+impl ProcSource {
+    pub fn new(proc_dir: impl Into<PathBuf>) -> Self {
+        Self { proc_dir: proc_dir.into() }
+    }
+}
+
+impl Default for ProcSource {
+    fn default() -> Self {
+        Self::new("/proc")
+    }
+}
+
+impl FocusSource for ProcSource {
+    fn applications(&mut self) -> Result<Vec<String>, AzeronCliError> {
+        let mut applications = vec![];
+        for entry in fs::read_dir(&self.proc_dir)? {
+            let entry = entry?;
+            if !entry.file_name().to_string_lossy().bytes().all(|b| b.is_ascii_digit()) {
+                continue;
+            }
+            // processes can exit while we're looking at them, so one that can't be read is gone
+            if let Some(name) = process_name(&entry.path()) {
+                applications.push(name);
+            }
+        }
+
+        Ok(applications)
+    }
+}
+
+/// The file name of the program a process was started as, e.g. `eldenring.exe` for `Z:\Games\ELDEN RING\eldenring.exe` under Wine.
+///
+/// Unlike `comm` it isn't cut to `COMM_LENGTH` bytes. Processes without a command line, like kernel threads, fall back to `comm`.
+fn process_name(process_dir: &Path) -> Option<String> {
+    let cmdline = fs::read(process_dir.join("cmdline")).ok()?;
+    let program = String::from_utf8_lossy(cmdline.split(|b| *b == 0).next().unwrap_or_default()).to_string();
+    match program.rsplit(['/', '\\']).next().map(str::trim) {
+        Some(name) if !name.is_empty() => Some(name.to_string()),
+        _ => fs::read_to_string(process_dir.join("comm")).ok().map(|comm| comm.trim().to_string()),
+    }
+}
+
+/// The application last reported over the daemon socket, e.g. by a window manager hook.
+///
+/// Clones share the same state, one is handed to the daemon to update and one is polled.
+#[derive(Clone, Default)]
+pub struct SharedFocus {
+    application: Arc<Mutex<Option<String>>>,
+}
+
+impl SharedFocus {
+    pub fn set(&self, application: Option<String>) {
+        *self.application.lock().unwrap_or_else(|e| e.into_inner()) = application;
+    }
+}
+
+impl FocusSource for SharedFocus {
+    fn applications(&mut self) -> Result<Vec<String>, AzeronCliError> {
+        Ok(self.application.lock().unwrap_or_else(|e| e.into_inner()).iter().cloned().collect())
+    }
+}
+
+/// The focused application, as printed by a command.
+pub struct CommandSource {
+    command: String,
+}
+
+impl CommandSource {
+    /// Prints the process name of the focused window on X11, needs `xdotool`.
+    pub const X11_FOCUSED_WINDOW: &'static str = "cat /proc/$(xdotool getactivewindow getwindowpid)/comm";
+
+    /// `command` is run with `sh -c`, its output is the name of the focused application.
+    pub fn new(command: impl Into<String>) -> Self {
+        Self { command: command.into() }
+    }
+}
+
+impl Default for CommandSource {
+    fn default() -> Self {
+        Self::new(Self::X11_FOCUSED_WINDOW)
+    }
+}
+
+impl FocusSource for CommandSource {
+    fn applications(&mut self) -> Result<Vec<String>, AzeronCliError> {
+        let output = Command::new("sh").arg("-c").arg(&self.command).output()?;
+        let application = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        // nothing focused, e.g. on an empty desktop
+        if !output.status.success() || application.is_empty() {
+            return Ok(vec![]);
+        }
+
+        Ok(vec![application])
+    }
+}
+
+/// What to switch to when a rule matches.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SwitchTarget {
+    /// One of the hardware profile slots.
+    Slot(u32),
+    /// A profile from the library, loaded into the software slot.
+    Profile(String),
+}
+
+impl Display for SwitchTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SwitchTarget::Slot(slot) => write!(f, "slot {}", slot),
+            SwitchTarget::Profile(name) => write!(f, "profile {}", name),
+        }
+    }
+}
+
+/// `/proc/<pid>/comm` cuts process names to this many bytes, `Cyberpunk2077.exe` becomes `Cyberpunk2077.e`.
+const COMM_LENGTH: usize = 15;
+
+/// Switches to `target` while `application` is in use, parsed from `application=target`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwitchRule {
+    pub application: String,
+    pub target: SwitchTarget,
+}
+
+impl SwitchRule {
+    /// Whether the rule is for `application`, which may be a name cut short like in `/proc/<pid>/comm` (as the X11 source prints).
+    pub fn matches(&self, application: &str) -> bool {
+        self.application == application || (application.len() == COMM_LENGTH && self.application.starts_with(application))
+    }
+}
+
+impl FromStr for SwitchRule {
+    type Err = String;
+
+    /// A target that is a number is a hardware slot, anything else is the name of a library profile.
+    fn from_str(rule: &str) -> Result<Self, String> {
+        let (application, target) = rule.split_once('=').ok_or_else(|| format!("expected APPLICATION=PROFILE, got {}", rule))?;
+        if application.is_empty() || target.is_empty() {
+            return Err(format!("expected APPLICATION=PROFILE, got {}", rule));
+        }
+        let target = match target.parse() {
+            Ok(slot) => SwitchTarget::Slot(slot),
+            Err(_) => SwitchTarget::Profile(target.to_string()),
+        };
+
+        Ok(Self { application: application.to_string(), target })
+    }
+}
+
+/// Decides when to switch profiles, based on the applications a `FocusSource` reports.
+///
+/// The first rule that matches any of the applications wins. When nothing matches, the current profile is kept.
+pub struct AutoSwitcher {
+    rules: Vec<SwitchRule>,
+    current: Option<SwitchTarget>,
+}
+
+impl AutoSwitcher {
+    pub fn new(rules: Vec<SwitchRule>) -> Self {
+        Self { rules, current: None }
+    }
+
+    /// Asks the source what's in use, returning the target to switch to if it changed.
+    ///
+    /// The target is returned again on the next poll until `switched` is called with it.
+    pub fn poll(&mut self, source: &mut dyn FocusSource) -> Result<Option<SwitchTarget>, AzeronCliError> {
+        let applications = source.applications()?;
+        let matched = self
+            .rules
+            .iter()
+            .find(|rule| applications.iter().any(|application| rule.matches(application)))
+            .map(|rule| rule.target.clone());
+
+        match matched {
+            Some(target) if self.current.as_ref() != Some(&target) => Ok(Some(target)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Remembers that the switch to a target returned by `poll` succeeded.
+    pub fn switched(&mut self, target: SwitchTarget) {
+        self.current = Some(target);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{AutoSwitcher, FocusSource, ProcSource, SharedFocus, SwitchRule, SwitchTarget};
+    use crate::azeron::AzeronCliError;
+
+    fn rules(rules: &[&str]) -> Vec<SwitchRule> {
+        rules.iter().map(|r| r.parse().unwrap()).collect()
+    }
+
+    /// Polls and, like the daemon after a successful switch, remembers the target.
+    fn poll_and_switch(switcher: &mut AutoSwitcher, source: &mut dyn FocusSource) -> Option<SwitchTarget> {
+        let target = switcher.poll(source).unwrap();
+        if let Some(target) = &target {
+            switcher.switched(target.clone());
+        }
+        target
+    }
+
+    struct Processes(Vec<&'static str>);
+
+    impl FocusSource for Processes {
+        fn applications(&mut self) -> Result<Vec<String>, AzeronCliError> {
+            Ok(self.0.iter().map(|p| p.to_string()).collect())
+        }
+    }
+
+    #[test]
+    fn reads_process_names_from_proc() {
+        let proc_dir = std::env::temp_dir().join(format!("azeron-cli-proc-{}", std::process::id()));
+        let processes = [
+            ("1", "/sbin/init\0splash\0", "systemd\n"),
+            // a kernel thread, without a command line
+            ("2", "", "kthreadd\n"),
+            ("4242", "Z:\\Games\\Cyberpunk 2077\\bin\\x64\\Cyberpunk2077.exe\0", "Cyberpunk2077.e\n"),
+        ];
+        for (pid, cmdline, comm) in processes {
+            fs::create_dir_all(proc_dir.join(pid)).unwrap();
+            fs::write(proc_dir.join(pid).join("cmdline"), cmdline).unwrap();
+            fs::write(proc_dir.join(pid).join("comm"), comm).unwrap();
+        }
+        fs::create_dir_all(proc_dir.join("self")).unwrap();
+        // a process that exited after its directory was listed
+        fs::create_dir_all(proc_dir.join("4343")).unwrap();
+
+        let mut applications = ProcSource::new(&proc_dir).applications().unwrap();
+        fs::remove_dir_all(&proc_dir).unwrap();
+        applications.sort();
+
+        assert_eq!(applications, ["Cyberpunk2077.exe", "init", "kthreadd"]);
+    }
+
+    #[test]
+    fn switches_once_per_change() {
+        let mut switcher = AutoSwitcher::new(rules(&["eldenring.exe=souls", "forza=1"]));
+        let mut focus = SharedFocus::default();
+
+        let mut poll = |application: Option<&str>| {
+            focus.set(application.map(|a| a.to_string()));
+            poll_and_switch(&mut switcher, &mut focus)
+        };
+
+        assert_eq!(poll(Some("eldenring.exe")), Some(SwitchTarget::Profile("souls".to_string())));
+        assert_eq!(poll(Some("eldenring.exe")), None);
+        assert_eq!(poll(Some("firefox")), None);
+        assert_eq!(poll(Some("forza")), Some(SwitchTarget::Slot(1)));
+        assert_eq!(poll(None), None);
+        assert!("no-target".parse::<SwitchRule>().is_err());
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let mut switcher = AutoSwitcher::new(rules(&["eldenring.exe=souls", "forza=1"]));
+
+        // the processes come in the opposite order of the rules
+        let target = poll_and_switch(&mut switcher, &mut Processes(vec!["firefox", "forza", "eldenring.exe"]));
+
+        assert_eq!(target, Some(SwitchTarget::Profile("souls".to_string())));
+    }
+
+    #[test]
+    fn retries_switches_that_failed() {
+        let mut switcher = AutoSwitcher::new(rules(&["forza=1"]));
+        let mut processes = Processes(vec!["forza"]);
+
+        assert_eq!(switcher.poll(&mut processes).unwrap(), Some(SwitchTarget::Slot(1)));
+        // the switch failed, so it wasn't confirmed with `switched`
+        assert_eq!(poll_and_switch(&mut switcher, &mut processes), Some(SwitchTarget::Slot(1)));
+        assert_eq!(switcher.poll(&mut processes).unwrap(), None);
+    }
+
+    #[test]
+    fn matches_names_cut_short_by_comm() {
+        let mut switcher = AutoSwitcher::new(rules(&["Cyberpunk2077.exe=cyberpunk", "eldenring.exe=souls"]));
+
+        // only names of the full `comm` length were cut, shorter ones have to match exactly
+        assert_eq!(switcher.poll(&mut Processes(vec!["eldenring"])).unwrap(), None);
+        assert_eq!(switcher.poll(&mut Processes(vec!["Cyberpunk2077.e"])).unwrap(), Some(SwitchTarget::Profile("cyberpunk".to_string())));
+    }
+}
@@ -0,0 +1,271 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use super::{communication::{AnalogType, FirmwareVersion}, AzeronCliError};
+
+/// Wraps a command into the `^<len>~<payload>\n` frame the device expects, prefixed with the report ID.
+pub fn to_azeron_message(message: &str) -> Result<Vec<u8>, AzeronCliError> {
+    // ensure message contains only ascii characters
+    if message.chars().any(|c| !c.is_ascii()) {
+        return Err(AzeronCliError::InvalidMessage { message: message.to_string() });
+    }
+    let bytes = message.as_bytes();
+    // get length of bytes as ascii characters
+    let length = bytes.len();
+    // convert length to ascii characters
+    let length_str = length.to_string();
+    let length_bytes = length_str.as_bytes();
+
+    // message is ^length_bytes~message\n
+    let mut message_bytes = vec![0, b'^'];
+    message_bytes.extend_from_slice(length_bytes);
+    message_bytes.push(b'~');
+    message_bytes.extend_from_slice(bytes);
+    message_bytes.push(b'\n');
+
+    Ok(message_bytes)
+}
+
+/// Reassembles `^<len>~<payload>\n` frames from the 64-byte reports the device sends.
+///
+/// Replies that aren't framed are passed through as a single report of text.
+#[derive(Debug, Default)]
+pub struct FrameDecoder {
+    buffer: Vec<u8>,
+}
+
+impl FrameDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the decoder has started a frame that needs more reports to complete.
+    pub fn is_pending(&self) -> bool {
+        !self.buffer.is_empty()
+    }
+
+    /// Feeds a report into the decoder, returning the payload once a frame is complete.
+    pub fn push(&mut self, report: &[u8]) -> Result<Option<String>, AzeronCliError> {
+        // reports are padded with zeros up to their full size
+        let end = report.iter().rposition(|b| *b != 0).map_or(0, |i| i + 1);
+        let report = &report[..end];
+
+        if !self.is_pending() && report.first() != Some(&b'^') {
+            if report.is_empty() || !report.is_ascii() {
+                return Err(malformed(report));
+            }
+            return Ok(Some(String::from_utf8_lossy(report).trim_end().to_string()));
+        }
+
+        self.buffer.extend_from_slice(report);
+        let result = self.parse();
+        if !matches!(result, Ok(None)) {
+            self.buffer.clear();
+        }
+
+        result
+    }
+
+    fn parse(&self) -> Result<Option<String>, AzeronCliError> {
+        let separator = match self.buffer.iter().position(|b| *b == b'~') {
+            Some(separator) => separator,
+            None if self.buffer[1..].iter().all(u8::is_ascii_digit) => return Ok(None),
+            None => return Err(malformed(&self.buffer)),
+        };
+        let length = std::str::from_utf8(&self.buffer[1..separator])
+            .ok()
+            .and_then(|length| length.parse::<usize>().ok())
+            .ok_or_else(|| malformed(&self.buffer))?;
+
+        let payload = &self.buffer[separator + 1..];
+        if payload.len() <= length {
+            return Ok(None);
+        }
+        if payload[length] != b'\n' || !payload[..length].is_ascii() {
+            return Err(malformed(&self.buffer));
+        }
+
+        Ok(Some(String::from_utf8_lossy(&payload[..length]).to_string()))
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str, reply: &str) -> Result<T, AzeronCliError> {
+    value.parse().map_err(|_| AzeronCliError::MalformedResponse { response: reply.to_string() })
+}
+
+fn malformed(bytes: &[u8]) -> AzeronCliError {
+    AzeronCliError::MalformedResponse { response: String::from_utf8_lossy(bytes).to_string() }
+}
+
+/// A decoded reply from the device.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum Reply {
+    /// `FWV_60_0`
+    FirmwareVersion(FirmwareVersion),
+    /// `FWT_1_3_0_0`
+    KeypadInfo(Vec<u32>),
+    /// `LEDS_ON` or `LEDS_OFF`
+    LEDState(bool),
+    /// `LEDBRT_5`
+    LEDBrightness(u8),
+    /// `ANALOG_SQUARE` or `ANALOG_CIRCLE`
+    AnalogType(AnalogType),
+    /// `ST0_21` (button 21, state 0)
+    SwitchState { id: u8, state: u8 },
+    /// `BTNTHT_10`
+    ButtonThrottle(u8),
+    /// `THROTTLE_TYPE_1`
+    ThrottleType(u8),
+    /// `HWANLGOFST_-5_-8`
+    HardwareAnalogOffset { x: i16, y: i16 },
+    /// `HWLWRDZ_0`
+    HardwareLowerDeadzone(u16),
+    /// `HWUPRDZ_298`
+    HardwareUpperDeadzone(u16),
+    /// `BOK_15`
+    ButtonSet { id: u8 },
+    /// `PROFILE_1`
+    ActiveProfile(u32),
+    /// Any reply without a known format, e.g. the profile list.
+    Other(String),
+}
+
+impl Reply {
+    pub fn parse(reply: &str) -> Result<Self, AzeronCliError> {
+        let malformed = || AzeronCliError::MalformedResponse { response: reply.to_string() };
+
+        let parsed = if reply.starts_with("FWV_") {
+            Reply::FirmwareVersion(FirmwareVersion::parse(reply)?)
+        } else if let Some(info) = reply.strip_prefix("FWT_") {
+            Reply::KeypadInfo(info.split('_').map(|i| parse_number(i, reply)).collect::<Result<_, _>>()?)
+        } else if let Some(state) = reply.strip_prefix("LEDS_") {
+            match state {
+                "ON" => Reply::LEDState(true),
+                "OFF" => Reply::LEDState(false),
+                _ => return Err(malformed()),
+            }
+        } else if let Some(brightness) = reply.strip_prefix("LEDBRT_") {
+            Reply::LEDBrightness(parse_number(brightness, reply)?)
+        } else if let Some(analog_type) = reply.strip_prefix("ANALOG_") {
+            match analog_type {
+                "SQUARE" => Reply::AnalogType(AnalogType::Square),
+                "CIRCLE" => Reply::AnalogType(AnalogType::Circle),
+                _ => return Err(malformed()),
+            }
+        } else if let Some(throttle) = reply.strip_prefix("BTNTHT_") {
+            Reply::ButtonThrottle(parse_number(throttle, reply)?)
+        } else if let Some(throttle_type) = reply.strip_prefix("THROTTLE_TYPE_") {
+            Reply::ThrottleType(parse_number(throttle_type, reply)?)
+        } else if let Some(offset) = reply.strip_prefix("HWANLGOFST_") {
+            let (x, y) = offset.split_once('_').ok_or_else(malformed)?;
+            Reply::HardwareAnalogOffset { x: parse_number(x, reply)?, y: parse_number(y, reply)? }
+        } else if let Some(deadzone) = reply.strip_prefix("HWLWRDZ_") {
+            Reply::HardwareLowerDeadzone(parse_number(deadzone, reply)?)
+        } else if let Some(deadzone) = reply.strip_prefix("HWUPRDZ_") {
+            Reply::HardwareUpperDeadzone(parse_number(deadzone, reply)?)
+        } else if let Some(profile_id) = reply.strip_prefix("PROFILE_") {
+            Reply::ActiveProfile(parse_number(profile_id, reply)?)
+        } else if let Some(id) = reply.strip_prefix("BOK_") {
+            Reply::ButtonSet { id: parse_number(id, reply)? }
+        } else if let Some((state, id)) = reply
+            .strip_prefix("ST")
+            .filter(|switch| switch.starts_with(|c: char| c.is_ascii_digit()))
+            .and_then(|switch| switch.split_once('_')) {
+            Reply::SwitchState { id: parse_number(id, reply)?, state: parse_number(state, reply)? }
+        } else {
+            Reply::Other(reply.to_string())
+        };
+
+        Ok(parsed)
+    }
+}
+
+impl Display for Reply {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Reply::FirmwareVersion(version) => write!(f, "FWV_{}_{}", version.major, version.minor),
+            Reply::KeypadInfo(info) => write!(f, "FWT_{}", info.iter().map(|i| i.to_string()).collect::<Vec<_>>().join("_")),
+            Reply::LEDState(enabled) => write!(f, "LEDS_{}", if *enabled { "ON" } else { "OFF" }),
+            Reply::LEDBrightness(brightness) => write!(f, "LEDBRT_{}", brightness),
+            Reply::AnalogType(AnalogType::Square) => write!(f, "ANALOG_SQUARE"),
+            Reply::AnalogType(AnalogType::Circle) => write!(f, "ANALOG_CIRCLE"),
+            Reply::SwitchState { id, state } => write!(f, "ST{}_{}", state, id),
+            Reply::ButtonThrottle(throttle) => write!(f, "BTNTHT_{}", throttle),
+            Reply::ThrottleType(throttle_type) => write!(f, "THROTTLE_TYPE_{}", throttle_type),
+            Reply::HardwareAnalogOffset { x, y } => write!(f, "HWANLGOFST_{}_{}", x, y),
+            Reply::HardwareLowerDeadzone(deadzone) => write!(f, "HWLWRDZ_{}", deadzone),
+            Reply::HardwareUpperDeadzone(deadzone) => write!(f, "HWUPRDZ_{}", deadzone),
+            Reply::ButtonSet { id } => write!(f, "BOK_{}", id),
+            Reply::ActiveProfile(profile_id) => write!(f, "PROFILE_{}", profile_id),
+            Reply::Other(reply) => write!(f, "{}", reply),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::{to_azeron_message, FrameDecoder, Reply};
+
+    /// Splits an encoded frame into zero-padded 64-byte reports, the way the device sends them.
+    fn to_reports(frame: &[u8]) -> Vec<Vec<u8>> {
+        frame[1..]
+            .chunks(64)
+            .map(|chunk| {
+                let mut report = chunk.to_vec();
+                report.resize(64, 0);
+                report
+            })
+            .collect()
+    }
+
+    fn decode(reports: &[Vec<u8>]) -> Option<String> {
+        let mut decoder = FrameDecoder::new();
+        let mut decoded = None;
+        for report in reports {
+            assert!(decoded.is_none(), "frame completed before the last report");
+            decoded = decoder.push(report).unwrap();
+        }
+        decoded
+    }
+
+    proptest! {
+        #[test]
+        fn round_trips_through_reports(message in "[ -~]{0,300}") {
+            let reports = to_reports(&to_azeron_message(&message).unwrap());
+            prop_assert_eq!(decode(&reports), Some(message));
+        }
+
+        #[test]
+        fn round_trips_known_replies(id in 1u8..=38, x in -512i16..=512, y in -512i16..=512, brightness in 0u8..=10) {
+            for reply in [Reply::ButtonSet { id }, Reply::HardwareAnalogOffset { x, y }, Reply::LEDBrightness(brightness)] {
+                let reports = to_reports(&to_azeron_message(&reply.to_string()).unwrap());
+                prop_assert_eq!(Reply::parse(&decode(&reports).unwrap()).unwrap(), reply);
+            }
+        }
+    }
+
+    #[test]
+    fn passes_through_unframed_replies() {
+        let mut report = b"BOK_15".to_vec();
+        report.resize(64, 0);
+
+        assert_eq!(FrameDecoder::new().push(&report).unwrap(), Some("BOK_15".to_string()));
+    }
+
+    #[test]
+    fn rejects_bad_length_prefix() {
+        assert!(FrameDecoder::new().push(b"^x~BOK_15\n").is_err());
+        assert!(FrameDecoder::new().push(b"^3~BOK_15\n").is_err());
+    }
+
+    #[test]
+    fn parses_documented_replies() {
+        assert_eq!(Reply::parse("ST0_21").unwrap(), Reply::SwitchState { id: 21, state: 0 });
+        assert_eq!(Reply::parse("FWT_1_3_0_0").unwrap(), Reply::KeypadInfo(vec![1, 3, 0, 0]));
+        assert_eq!(Reply::parse("PROFILE_1").unwrap(), Reply::ActiveProfile(1));
+        assert!(Reply::parse("LEDBRT_x").is_err());
+    }
+}
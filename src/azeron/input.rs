@@ -0,0 +1,70 @@
+use super::unsorted::JoystickZone;
+
+/// The state of the keypad as sent in an input report (message type 1).
+#[derive(Debug, Clone)]
+pub struct InputReport {
+    /// IDs of the buttons that are held down. The analog stick directions (24-27 and 32-35) are not reported as buttons.
+    pub pressed: Vec<u8>,
+    /// The analog stick position, 0-1023 on both axes with 512 being the center.
+    pub analog: JoystickZone,
+    /// The second analog reading the firmware sends along, before offsets and deadzones are applied.
+    pub analog_raw: JoystickZone,
+}
+
+impl InputReport {
+    /// Decodes a raw report, returning `None` if it isn't an input report.
+    pub fn from_report(packet: &[u8]) -> Option<Self> {
+        if packet.len() < 9 || packet[4] != 1 {
+            return None;
+        }
+        let payload = packet.get(9..9 + packet[8] as usize)?;
+        if payload.len() < 14 {
+            return None;
+        }
+
+        let pressed = (1..=38u8)
+            .filter(|id| !((24..=27).contains(id) || (32..=35).contains(id)))
+            .filter(|id| is_set(*id, payload))
+            .collect();
+
+        Some(Self {
+            pressed,
+            analog: JoystickZone {
+                x: u16::from_le_bytes([payload[10], payload[11]]) as i32,
+                y: u16::from_le_bytes([payload[12], payload[13]]) as i32,
+            },
+            analog_raw: JoystickZone {
+                x: u16::from_le_bytes([payload[6], payload[7]]) as i32,
+                y: u16::from_le_bytes([payload[8], payload[9]]) as i32,
+            },
+        })
+    }
+}
+
+/// Button states are a bit field over the first 6 bytes of the payload, starting with the last byte.
+fn is_set(byte: u8, bytes: &[u8]) -> bool {
+    let index = 5 - (byte - 1) / 8;
+    0 != (bytes[index as usize] & 1 << ((byte - 1) % 8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::InputReport;
+
+    #[test]
+    fn decodes_input_reports() {
+        let mut packet = [0u8; 64];
+        packet[4] = 1;
+        packet[8] = 14;
+        // button 1 and button 9
+        packet[9 + 5] = 0b0000_0001;
+        packet[9 + 4] = 0b0000_0001;
+        packet[9 + 10..9 + 14].copy_from_slice(&[0x00, 0x02, 0xff, 0x03]);
+
+        let report = InputReport::from_report(&packet).unwrap();
+
+        assert_eq!(report.pressed, vec![1, 9]);
+        assert_eq!((report.analog.x, report.analog.y), (512, 1023));
+        assert!(InputReport::from_report(b"BOK_15").is_none());
+    }
+}
@@ -4,11 +4,11 @@ pub fn key_name_to_device_value(key_name: &str) -> Result<u32, AzeronCliError> {
     if key_name.contains(" + ") {
         return Err(AzeronCliError::KeyCombinationsNotSupported);
     }
-    if is_modifier_key(key_name) {
-        return Ok(*REVERSED_DEVICE_KEYS.get(format!("MODIFIERKEY_LEFT_{}", key_name).as_str()).unwrap())
+    if let Some(key_code) = REVERSED_DEVICE_KEYS.get(format!("MODIFIERKEY_LEFT_{}", key_name).as_str()) {
+        return Ok(*key_code)
     }
-    if is_normal_key(key_name) {
-        return Ok(*REVERSED_DEVICE_KEYS.get(format!("KEY_{}", key_name).as_str()).unwrap())
+    if let Some(key_code) = REVERSED_DEVICE_KEYS.get(format!("KEY_{}", key_name).as_str()) {
+        return Ok(*key_code)
     }
 
     Err(AzeronCliError::KeyNotSupported { key_name: key_name.to_string() })
@@ -66,7 +66,7 @@ pub fn from_device_meta_key(key_name: &str) -> Result<String, AzeronCliError> {
     if key_name == "0" {
         return Ok("NONE".to_string());
     }
-    let key = DEVICE_KEYS.get(&(key_name.parse::<i32>().unwrap() as u32));
+    let key = key_name.parse::<u32>().ok().and_then(|key_code| DEVICE_KEYS.get(&key_code));
     if let Some(key) = key {
         if key.contains("_ALT") {
             return Ok("ALT".to_string());
@@ -89,12 +89,18 @@ pub fn from_js_key_code_to_robot_key(js_key_code: u32) -> Result<&'static str, A
     Err(AzeronCliError::UndefinedKeyCode { key_name: js_key_code.to_string() })
 }
 
-pub fn meta_key_to_device_code(meta_key: &str) -> u32 {
-    match meta_key.to_uppercase().as_str() {
-        "CTRL" => *REVERSED_DEVICE_KEYS.get("MODIFIERKEY_LEFT_CTRL").unwrap(),
-        "ALT" => *REVERSED_DEVICE_KEYS.get("MODIFIERKEY_LEFT_ALT").unwrap(),
-        "SHIFT" => *REVERSED_DEVICE_KEYS.get("MODIFIERKEY_LEFT_SHIFT").unwrap(),
-        "SUPER" => *REVERSED_DEVICE_KEYS.get("MODIFIERKEY_LEFT_GUI").unwrap(),
-        _ => 0
-    }
+pub fn meta_key_to_device_code(meta_key: &str) -> Result<u32, AzeronCliError> {
+    let device_key = match meta_key.to_uppercase().as_str() {
+        "NONE" | "0" => return Ok(0),
+        "CTRL" => "MODIFIERKEY_LEFT_CTRL",
+        "ALT" => "MODIFIERKEY_LEFT_ALT",
+        "SHIFT" => "MODIFIERKEY_LEFT_SHIFT",
+        "SUPER" => "MODIFIERKEY_LEFT_GUI",
+        _ => return Err(AzeronCliError::UnknownMetaKey { key_name: meta_key.to_string() }),
+    };
+
+    REVERSED_DEVICE_KEYS
+        .get(device_key)
+        .copied()
+        .ok_or(AzeronCliError::UnknownMetaKey { key_name: meta_key.to_string() })
 }
\ No newline at end of file
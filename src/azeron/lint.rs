@@ -0,0 +1,191 @@
+use std::{collections::HashSet, fmt::Display};
+
+use super::{
+    button::{Button, ButtonType},
+    key_manager::{from_device_meta_key, is_supported_on_hardware, key_code_to_name, meta_key_to_device_code},
+    macros::MacroStepType,
+    statics::{ANALOG_STICK_PINS, BUTTON_IDS_TO_PINS, MAX_KEY_VALUES, MAX_META_KEYS},
+    unsorted::{Profile, StringOrU32},
+};
+
+/// Macro steps that wait or hold a key longer than this are most likely a typo.
+const MAX_MACRO_DELAY_MS: u32 = 60_000;
+
+/// Something in a profile the keypad would reject, cut off or misbehave on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    /// The button the problem is with, if it is with a single button.
+    pub button_id: Option<u8>,
+    pub message: String,
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.button_id {
+            Some(button_id) => write!(f, "button {}: {}", button_id, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Checks a profile before it is written to the keypad, returning every problem found.
+pub fn check_profile(profile: &Profile) -> Vec<Problem> {
+    let mut problems = vec![];
+    let mut seen = HashSet::new();
+    for button in &profile.buttons {
+        let mut problem = |message: String| problems.push(Problem { button_id: Some(button.id), message });
+        if !seen.insert(button.id) {
+            problem("is defined more than once".to_string());
+        }
+        match BUTTON_IDS_TO_PINS.get(&button.id) {
+            None => problem("there is no button with this ID".to_string()),
+            Some(pins) if *pins != button.pins => problem(format!("has pins {:?}, but the button is on {:?}", button.pins, pins)),
+            Some(pins) if button.is_analog_joystick() && *pins != ANALOG_STICK_PINS => problem(format!("is set to {:?}, but isn't part of the analog stick", button.button_type)),
+            Some(_) => {}
+        }
+        if button.key_values.len() > MAX_KEY_VALUES {
+            problem(format!("has {} key values, only {} are sent", button.key_values.len(), MAX_KEY_VALUES));
+        }
+        if button.meta_keys.len() > MAX_META_KEYS {
+            problem(format!("has {} meta keys, only {} are sent", button.meta_keys.len(), MAX_META_KEYS));
+        }
+        check_keys(button).into_iter().for_each(&mut problem);
+        check_macro(button).into_iter().for_each(&mut problem);
+    }
+    problems.extend(check_stick_directions(profile));
+
+    problems
+}
+
+fn check_keys(button: &Button) -> Vec<String> {
+    let mut problems = vec![];
+    for (index, key_value) in button.key_values.iter().enumerate().filter(|(index, _)| button.holds_key_code(*index)) {
+        let key_code = match key_value {
+            StringOrU32::U32(key_code) => *key_code,
+            StringOrU32::String(s) => match s.parse() {
+                Ok(key_code) => key_code,
+                Err(_) => {
+                    problems.push(format!("key value {} ({}) isn't a key code", index, s));
+                    continue;
+                }
+            },
+        };
+        match key_code_to_name(key_code) {
+            Err(_) => problems.push(format!("key value {} ({}) is an unknown key code", index, key_code)),
+            Ok(name) if !is_supported_on_hardware(key_code) => problems.push(format!("key value {} ({}) can't be sent by the keypad", index, name)),
+            Ok(_) => {}
+        }
+    }
+    // meta keys of sticks and the like hold settings instead
+    if button.button_type == ButtonType::KeyboardKey {
+        for meta_key in &button.meta_keys {
+            let known = match meta_key {
+                StringOrU32::String(s) => meta_key_to_device_code(s).is_ok(),
+                StringOrU32::U32(0) => true,
+                StringOrU32::U32(u) => from_device_meta_key(&u.to_string()).is_ok(),
+            };
+            if !known {
+                problems.push(format!("{} is an unknown meta key", meta_key));
+            }
+        }
+    }
+
+    problems
+}
+
+fn check_macro(button: &Button) -> Vec<String> {
+    if button.button_type != ButtonType::Macro {
+        return vec![];
+    }
+    if button.button_macro.steps.is_empty() {
+        return vec!["is a macro without steps".to_string()];
+    }
+
+    let mut problems = vec![];
+    for (index, step) in button.button_macro.steps.iter().enumerate() {
+        if step.macro_type == MacroStepType::Timeout {
+            match step.value.parse::<u32>() {
+                Ok(0) | Err(_) => problems.push(format!("macro step {} waits for {:?}, expected a number of milliseconds", index, step.value)),
+                Ok(delay) if delay > MAX_MACRO_DELAY_MS => problems.push(format!("macro step {} waits for {} ms, more than {} ms", index, delay, MAX_MACRO_DELAY_MS)),
+                Ok(_) => {}
+            }
+        } else if step.hold_down_for > MAX_MACRO_DELAY_MS {
+            problems.push(format!("macro step {} holds the key for {} ms, more than {} ms", index, step.hold_down_for, MAX_MACRO_DELAY_MS));
+        }
+    }
+
+    problems
+}
+
+/// The kind of stick direction a button type is, if it is one.
+fn stick_direction_kind(button_type: ButtonType) -> Option<&'static str> {
+    match button_type {
+        ButtonType::AnalogJoystickWithKeysUp
+        | ButtonType::AnalogJoystickWithKeysRight
+        | ButtonType::AnalogJoystickWithKeysDown
+        | ButtonType::AnalogJoystickWithKeysLeft => Some("keys"),
+        ButtonType::AnalogJoystickAndWithKeysUp
+        | ButtonType::AnalogJoystickAndWithKeysRight
+        | ButtonType::AnalogJoystickAndWithKeysDown
+        | ButtonType::AnalogJoystickAndWithKeysLeft => Some("analog and keys"),
+        ButtonType::AnalogJoystickWithDriftAndKeysUp
+        | ButtonType::AnalogJoystickWithDriftAndKeysRight
+        | ButtonType::AnalogJoystickWithDriftAndKeysDown
+        | ButtonType::AnalogJoystickWithDriftAndKeysLeft => Some("drift and keys"),
+        _ => None,
+    }
+}
+
+/// The directions of the stick have to agree on how the stick is used.
+fn check_stick_directions(profile: &Profile) -> Vec<Problem> {
+    let mut directions = profile.buttons.iter().filter_map(|b| stick_direction_kind(b.button_type).map(|kind| (b.id, kind)));
+    let (first_id, first_kind) = match directions.next() {
+        Some(direction) => direction,
+        None => return vec![],
+    };
+
+    directions
+        .filter(|(_, kind)| *kind != first_kind)
+        .map(|(button_id, kind)| Problem {
+            button_id: Some(button_id),
+            message: format!("is a stick direction with {}, but button {} is one with {}", kind, first_id, first_kind),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check_profile;
+    use crate::azeron::{
+        button::{Button, ButtonType},
+        unsorted::Profile,
+    };
+
+    #[test]
+    fn finds_problems_in_profiles() {
+        let mut profile = Profile::new(0, "broken");
+        profile.buttons = vec![
+            Button::new(1, ButtonType::KeyboardKey, [26, 255], vec![70.into()], vec!["CTRL".to_string().into()]),
+            Button::new(1, ButtonType::KeyboardKey, [26, 255], vec![70.into()], vec![]),
+            Button::new(2, ButtonType::KeyboardKey, [0, 255], vec![999.into(), 65.into(), 66.into(), 67.into(), 68.into()], vec!["HYPER".to_string().into()]),
+            Button::new(24, ButtonType::AnalogJoystickWithKeysUp, [39, 40], vec![87.into()], vec![]),
+            Button::new(25, ButtonType::AnalogJoystickAndWithKeysRight, [39, 40], vec![68.into()], vec![]),
+            Button::new(50, ButtonType::Disabled, [0, 0], vec![], vec![]),
+        ];
+
+        let problems = check_profile(&profile).iter().map(|p| p.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            problems,
+            [
+                "button 1: is defined more than once",
+                "button 2: has pins [0, 255], but the button is on [25, 255]",
+                "button 2: has 5 key values, only 4 are sent",
+                "button 2: key value 0 (999) is an unknown key code",
+                "button 2: HYPER is an unknown meta key",
+                "button 50: there is no button with this ID",
+                "button 25: is a stick direction with analog and keys, but button 24 is one with keys",
+            ]
+        );
+    }
+}
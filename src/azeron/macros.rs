@@ -1,6 +1,6 @@
 use serde::{Serialize, Deserialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct AzeronMacro {
     pub repeat: bool,
@@ -9,7 +9,7 @@ pub struct AzeronMacro {
     pub current_step: usize,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct AzeronMacroStep {
     #[serde(rename = "type")]
@@ -21,7 +21,7 @@ pub struct AzeronMacroStep {
     pub time_out_handle: u32,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
 pub enum MacroStepType {
     Timeout,
     ButtonPress,
@@ -4,6 +4,41 @@ pub mod key_manager;
 pub mod macros;
 pub mod button;
 pub mod communication;
+pub mod frame;
+pub mod transport;
+#[cfg(feature = "hid")]
+pub mod discovery;
+pub mod input;
+pub mod store;
+pub mod readable;
+pub mod lint;
+pub mod templates;
+pub mod diagram;
+pub mod focus;
+pub mod client;
+pub mod async_client;
+#[cfg(unix)]
+pub mod daemon;
+#[cfg(target_os = "linux")]
+pub mod doctor;
+
+#[cfg(feature = "hid")]
+pub use hidapi::HidError;
+
+/// Stands in for `hidapi::HidError` when building without the `hid` feature, it can't be constructed.
+#[cfg(not(feature = "hid"))]
+#[derive(Debug)]
+pub enum HidError {}
+
+#[cfg(not(feature = "hid"))]
+impl std::fmt::Display for HidError {
+    fn fmt(&self, _: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {}
+    }
+}
+
+#[cfg(not(feature = "hid"))]
+impl std::error::Error for HidError {}
 
 custom_error::custom_error!(
     pub AzeronCliError
@@ -12,4 +47,80 @@ custom_error::custom_error!(
     UndefinedKeyName { key_code: u32 } = "Undefined key name for key code: {key_code}",
     UndefinedKeyCode { key_name: String } = "Undefined key code for key name: {key_name}",
     UnknownMetaKey { key_name: String } = "Unknown meta key: {key_name}",
-);
\ No newline at end of file
+    ValueOutOfRange { name: String, value: i64, min: i64, max: i64 } = "Value {value} for {name} is out of range ({min}-{max})",
+    InvalidButtonId { button_id: u8 } = "Invalid button ID: {button_id}",
+    InvalidScriptLine { line: usize, message: String } = "Invalid command on line {line}: {message}",
+    UnsupportedInScript = "This command can't be used in a script",
+    InvalidMessage { message: String } = "Message must be non-empty ASCII without line breaks: {message}",
+    UnconfirmedCommand { command: String } = "{command} hasn't been confirmed against the official app yet, send it with `azeron-cli raw '{command}'` if you know it works",
+    DeviceNotFound = "No Azeron device found",
+    MultipleDevices { count: usize } = "Found {count} Azeron devices, select one with --device",
+    PermissionDenied { path: String } = "Permission denied while opening {path}",
+    Timeout = "Timed out waiting for a reply from the Azeron device",
+    MalformedResponse { response: String } = "Malformed response from the Azeron device: {response}",
+    Nak { response: String } = "The Azeron device rejected the request: {response}",
+    UnsupportedFirmware { version: String } = "Unsupported firmware version: {version}",
+    Hid { source: HidError } = "HID error: {source}",
+    Io { source: std::io::Error } = "I/O error: {source}",
+    Json { source: serde_json::Error } = "JSON error: {source}",
+    Toml { source: toml::de::Error } = "TOML error: {source}",
+    TomlOutput { source: toml::ser::Error } = "TOML error: {source}",
+    Yaml { source: serde_yaml::Error } = "YAML error: {source}",
+    MalformedTrace { data: String } = "Malformed trace data: {data}",
+    ReplayMismatch { expected: String, actual: String } = "Replayed trace expected {expected}, but got {actual}",
+    NotSoftwareProfile { name: String } = "{name} is a hardware profile, only software profiles can be loaded from the host",
+    ProfileNotFound { name: String } = "No profile named {name}",
+    ProfileExists { name: String } = "There already is a profile named {name}",
+    InvalidProfileName { name: String } = "Invalid profile name: {name}",
+    InvalidBinding { button: String, binding: String } = "Invalid binding for button {button}: {binding}",
+    MissingButtonId { label: String } = "Button {label} is keyed by its label, it needs an `id` as well",
+    ProfileCheckFailed { name: String, count: usize } = "Found {count} problem(s) in {name}",
+    UnknownTemplate { name: String, templates: String } = "Unknown template {name}, there are {templates}",
+    ButtonNotInProfile { button_id: u8, name: String } = "{name} has no button {button_id}",
+    DaemonNotRunning { path: String } = "No daemon is listening on {path}, start one with `azeron-cli daemon`",
+    DaemonRunning { path: String } = "A daemon is already listening on {path}",
+    Daemon { message: String, code: i32 } = "{message}",
+);
+
+impl AzeronCliError {
+    /// The exit code the CLI uses for this kind of error, so scripts can tell failures apart.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            AzeronCliError::KeyNotSupported { .. }
+            | AzeronCliError::KeyCombinationsNotSupported
+            | AzeronCliError::UndefinedKeyName { .. }
+            | AzeronCliError::UndefinedKeyCode { .. }
+            | AzeronCliError::UnknownMetaKey { .. }
+            | AzeronCliError::ValueOutOfRange { .. }
+            | AzeronCliError::InvalidButtonId { .. }
+            | AzeronCliError::InvalidScriptLine { .. }
+            | AzeronCliError::UnsupportedInScript
+            | AzeronCliError::InvalidMessage { .. }
+            | AzeronCliError::UnconfirmedCommand { .. }
+            | AzeronCliError::NotSoftwareProfile { .. }
+            | AzeronCliError::ProfileNotFound { .. }
+            | AzeronCliError::ProfileExists { .. }
+            | AzeronCliError::InvalidProfileName { .. }
+            | AzeronCliError::InvalidBinding { .. }
+            | AzeronCliError::MissingButtonId { .. }
+            | AzeronCliError::ProfileCheckFailed { .. }
+            | AzeronCliError::UnknownTemplate { .. }
+            | AzeronCliError::ButtonNotInProfile { .. } => 2,
+            AzeronCliError::DeviceNotFound | AzeronCliError::MultipleDevices { .. } | AzeronCliError::DaemonRunning { .. } | AzeronCliError::DaemonNotRunning { .. } => 3,
+            AzeronCliError::PermissionDenied { .. } => 4,
+            AzeronCliError::Timeout => 5,
+            AzeronCliError::MalformedResponse { .. } => 6,
+            AzeronCliError::Nak { .. } => 7,
+            AzeronCliError::UnsupportedFirmware { .. } => 8,
+            AzeronCliError::Hid { .. } => 9,
+            AzeronCliError::Io { .. }
+            | AzeronCliError::Json { .. }
+            | AzeronCliError::Toml { .. }
+            | AzeronCliError::TomlOutput { .. }
+            | AzeronCliError::Yaml { .. } => 10,
+            AzeronCliError::MalformedTrace { .. } | AzeronCliError::ReplayMismatch { .. } => 11,
+            // errors the daemon ran into are passed on with the code it would have exited with
+            AzeronCliError::Daemon { code, .. } => *code,
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,406 @@
+use std::{collections::BTreeMap, fmt::Display, fs, path::Path};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{
+    button::{Button, ButtonType},
+    macros::AzeronMacro,
+    statics::{BUTTON_IDS_TO_PINS, JAVASCRIPT_KEY_CODES, READABLE_TO_XINPUT, XINPUT_TO_READABLE},
+    unsorted::{Profile, StringOrU32},
+    AzeronCliError,
+};
+
+/// The meta keys a binding can hold. They are upper case, to tell them apart from keys like `Ctrl`.
+const META_KEYS: [&str; 4] = ["CTRL", "ALT", "SHIFT", "SUPER"];
+
+/// The format of a profile file, picked by its extension.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProfileFormat {
+    /// The format of the official app, with raw key codes.
+    Json,
+    /// A `ReadableProfile` in TOML.
+    Toml,
+    /// A `ReadableProfile` in YAML.
+    Yaml,
+}
+
+impl ProfileFormat {
+    /// `.toml` and `.yaml`/`.yml` files are readable profiles, anything else is taken to be JSON as exported by the official app.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase()).as_deref() {
+            Some("toml") => ProfileFormat::Toml,
+            Some("yaml" | "yml") => ProfileFormat::Yaml,
+            _ => ProfileFormat::Json,
+        }
+    }
+
+    pub fn parse(self, contents: &str) -> Result<Profile, AzeronCliError> {
+        match self {
+            ProfileFormat::Json => Ok(serde_json::from_str(contents)?),
+            ProfileFormat::Toml => toml::from_str::<ReadableProfile>(contents)?.into_profile(),
+            ProfileFormat::Yaml => serde_yaml::from_str::<ReadableProfile>(contents)?.into_profile(),
+        }
+    }
+
+    pub fn write(self, profile: &Profile) -> Result<String, AzeronCliError> {
+        match self {
+            ProfileFormat::Json => Ok(serde_json::to_string_pretty(profile)? + "\n"),
+            ProfileFormat::Toml => Ok(toml::to_string(&ReadableProfile::from_profile(profile))?),
+            ProfileFormat::Yaml => Ok(serde_yaml::to_string(&ReadableProfile::from_profile(profile))?),
+        }
+    }
+}
+
+/// Reads a profile file in whatever format its extension says.
+pub fn read_profile_file(path: &Path) -> Result<Profile, AzeronCliError> {
+    ProfileFormat::from_path(path).parse(&fs::read_to_string(path)?)
+}
+
+/// Writes a profile file in whatever format its extension says.
+pub fn write_profile_file(path: &Path, profile: &Profile) -> Result<(), AzeronCliError> {
+    fs::write(path, ProfileFormat::from_path(path).write(profile)?)?;
+
+    Ok(())
+}
+
+/// A profile meant to be written and reviewed by people.
+///
+/// Buttons are keyed by their ID, or by their label with the ID next to the binding. Bindings
+/// are names instead of key codes and pins, anything that has no name is kept as raw values:
+///
+/// ```toml
+/// name = "racing"
+///
+/// [buttons]
+/// 1 = "Space"
+/// 14 = "CTRL"
+/// 15 = "CTRL + F"
+/// 22 = "xbox:A"
+/// 23 = "mouse:1"
+///
+/// [buttons.boost]
+/// id = 24
+/// bind = "SHIFT + W"
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ReadableProfile {
+    pub name: String,
+    #[serde(default)]
+    pub id: u32,
+    #[serde(default = "is_software_by_default")]
+    pub software: bool,
+    #[serde(default)]
+    pub favourite: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub favourited_at: Option<String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub hash: String,
+    // TOML wants the plain bindings before the tables
+    #[serde(default, serialize_with = "toml::ser::tables_last")]
+    pub buttons: BTreeMap<ButtonKey, Binding>,
+}
+
+fn is_software_by_default() -> bool {
+    true
+}
+
+/// What a binding is keyed by, button IDs sort before labels.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ButtonKey {
+    Id(u8),
+    Label(String),
+}
+
+impl Display for ButtonKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ButtonKey::Id(id) => write!(f, "{}", id),
+            ButtonKey::Label(label) => write!(f, "{}", label),
+        }
+    }
+}
+
+impl Serialize for ButtonKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // TOML only has string keys
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for ButtonKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ButtonKeyVisitor;
+
+        impl<'de> de::Visitor<'de> for ButtonKeyVisitor {
+            type Value = ButtonKey;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, "a button ID or label")
+            }
+
+            fn visit_str<E: de::Error>(self, key: &str) -> Result<ButtonKey, E> {
+                Ok(key.parse().map(ButtonKey::Id).unwrap_or_else(|_| ButtonKey::Label(key.to_string())))
+            }
+
+            fn visit_u64<E: de::Error>(self, id: u64) -> Result<ButtonKey, E> {
+                u8::try_from(id).map(ButtonKey::Id).map_err(|_| E::invalid_value(de::Unexpected::Unsigned(id), &self))
+            }
+        }
+
+        deserializer.deserialize_any(ButtonKeyVisitor)
+    }
+}
+
+/// What a button does, either just its name or a table with the details.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Binding {
+    /// A binding like `CTRL + F`, `xbox:A`, `mouse:1`, `joystick:3`, `profile-switch`, `disabled` or `none`.
+    Name(String),
+    Button(ButtonEntry),
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ButtonEntry {
+    /// The button ID, needed when the button is keyed by its label.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bind: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// The raw button type for bindings without a name, a keyboard key if left out.
+    #[serde(default, rename = "type", skip_serializing_if = "Option::is_none")]
+    pub button_type: Option<ButtonType>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keys: Vec<StringOrU32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub meta: Vec<StringOrU32>,
+    /// Only needed if they differ from the usual pins of the button.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pins: Option<[u8; 2]>,
+    #[serde(default, rename = "macro", skip_serializing_if = "Option::is_none")]
+    pub button_macro: Option<AzeronMacro>,
+}
+
+impl ReadableProfile {
+    pub fn from_profile(profile: &Profile) -> Self {
+        let label_count = |label: &str| profile.buttons.iter().filter(|b| b.user_label == label).count();
+        let buttons = profile
+            .buttons
+            .iter()
+            .map(|button| {
+                let bind = binding_name(button);
+                let mut entry = ButtonEntry {
+                    pins: Some(button.pins).filter(|pins| BUTTON_IDS_TO_PINS.get(&button.id) != Some(pins)),
+                    button_macro: Some(button.button_macro.clone()).filter(|m| !m.steps.is_empty() || m.repeat),
+                    ..Default::default()
+                };
+                if bind.is_none() {
+                    entry.button_type = Some(button.button_type);
+                    entry.keys = button.key_values.clone();
+                    entry.meta = button.meta_keys.clone();
+                }
+                entry.bind = bind;
+
+                let label = &button.user_label;
+                let key = if label.is_empty() || label.parse::<u8>().is_ok() || label_count(label) > 1 {
+                    entry.label = Some(label.clone()).filter(|l| !l.is_empty());
+                    ButtonKey::Id(button.id)
+                } else {
+                    entry.id = Some(button.id);
+                    ButtonKey::Label(label.clone())
+                };
+
+                let binding = match entry {
+                    ButtonEntry { id: None, bind: Some(bind), label: None, button_type: None, pins: None, button_macro: None, .. } => Binding::Name(bind),
+                    entry => Binding::Button(entry),
+                };
+                (key, binding)
+            })
+            .collect();
+
+        Self {
+            name: profile.name.clone(),
+            id: profile.id,
+            software: profile.is_software,
+            favourite: profile.is_favourite,
+            favourited_at: profile.favourited_at.clone(),
+            hash: profile.hash.clone(),
+            buttons,
+        }
+    }
+
+    pub fn into_profile(self) -> Result<Profile, AzeronCliError> {
+        let mut profile = Profile::new(self.id, &self.name);
+        profile.is_software = self.software;
+        profile.is_favourite = self.favourite;
+        profile.favourited_at = self.favourited_at;
+        profile.hash = self.hash;
+
+        for (key, binding) in self.buttons {
+            let mut entry = match binding {
+                Binding::Name(bind) => ButtonEntry { bind: Some(bind), ..Default::default() },
+                Binding::Button(entry) => entry,
+            };
+            let (button_id, label) = match &key {
+                ButtonKey::Id(id) => (*id, entry.label.take()),
+                ButtonKey::Label(label) => (entry.id.ok_or_else(|| AzeronCliError::MissingButtonId { label: label.clone() })?, Some(label.clone())),
+            };
+            let pins = match entry.pins {
+                Some(pins) => pins,
+                None => *BUTTON_IDS_TO_PINS.get(&button_id).ok_or(AzeronCliError::InvalidButtonId { button_id })?,
+            };
+            let (button_type, key_values, meta_keys) = match &entry.bind {
+                Some(bind) => parse_binding(bind).ok_or_else(|| AzeronCliError::InvalidBinding { button: key.to_string(), binding: bind.clone() })?,
+                None => (entry.button_type.unwrap_or_default(), entry.keys, entry.meta),
+            };
+
+            let mut button = Button::new(button_id, button_type, pins, key_values, meta_keys);
+            button.user_label = label.unwrap_or_default();
+            if let Some(button_macro) = entry.button_macro {
+                button.button_macro = button_macro;
+            }
+            profile.add_button(button);
+        }
+        // labelled buttons come last in the file
+        profile.buttons.sort_by_key(|b| b.id);
+
+        Ok(profile)
+    }
+}
+
+/// The JavaScript key code of a key name, the lowest one if several keys share the name.
+pub fn js_key_code(key_name: &str) -> Option<u32> {
+    let codes = |matches: &dyn Fn(&str) -> bool| JAVASCRIPT_KEY_CODES.iter().filter(|(_, name)| matches(name)).map(|(code, _)| *code).min();
+
+    codes(&|name| name == key_name).or_else(|| codes(&|name| name.eq_ignore_ascii_case(key_name)))
+}
+
+/// The name of a button's binding, `None` if it can't be described by one.
+///
+/// Only names that turn back into exactly the same button are used.
+pub fn binding_name(button: &Button) -> Option<String> {
+    let single_value = match button.key_values.as_slice() {
+        [StringOrU32::U32(value)] => Some(*value),
+        _ => None,
+    };
+    match button.button_type {
+        ButtonType::KeyboardKey => {
+            let meta_keys = button.meta_keys.iter().map(|meta| match meta {
+                StringOrU32::String(meta) if META_KEYS.contains(&meta.as_str()) => Some(meta.clone()),
+                _ => None,
+            });
+            let keys = button.key_values.iter().map(|key| match key {
+                StringOrU32::U32(code) => JAVASCRIPT_KEY_CODES
+                    .get(code)
+                    .filter(|name| js_key_code(name) == Some(*code) && !name.contains(['+', ':']))
+                    .map(|name| name.to_string()),
+                StringOrU32::String(_) => None,
+            });
+            let names = meta_keys.chain(keys).collect::<Option<Vec<_>>>()?;
+            match names.is_empty() {
+                true => Some("none".to_string()),
+                false => Some(names.join(" + ")),
+            }
+        }
+        _ if !button.meta_keys.is_empty() => None,
+        ButtonType::MouseButton => Some(format!("mouse:{}", single_value?)),
+        ButtonType::JoystickButton => Some(format!("joystick:{}", single_value?)),
+        ButtonType::XInputButton => Some(format!("xbox:{}", XINPUT_TO_READABLE.get(&single_value?)?)),
+        ButtonType::XInputTrigger => match single_value? {
+            0 => Some("xbox:LT".to_string()),
+            1 => Some("xbox:RT".to_string()),
+            _ => None,
+        },
+        ButtonType::SwitchProfile if button.key_values.is_empty() => Some("profile-switch".to_string()),
+        ButtonType::Disabled if button.key_values.is_empty() => Some("disabled".to_string()),
+        _ => None,
+    }
+}
+
+/// Turns a binding name back into the button type, key values and meta keys it stands for.
+pub fn parse_binding(binding: &str) -> Option<(ButtonType, Vec<StringOrU32>, Vec<StringOrU32>)> {
+    let binding = binding.trim();
+    if let Some((kind, value)) = binding.split_once(':') {
+        let value = value.trim();
+        match kind.to_lowercase().as_str() {
+            "mouse" => return Some((ButtonType::MouseButton, vec![value.parse::<u32>().ok()?.into()], vec![])),
+            "joystick" => return Some((ButtonType::JoystickButton, vec![value.parse::<u32>().ok()?.into()], vec![])),
+            "xbox" => {
+                return match value.to_uppercase().as_str() {
+                    "LT" => Some((ButtonType::XInputTrigger, vec![0.into()], vec![])),
+                    "RT" => Some((ButtonType::XInputTrigger, vec![1.into()], vec![])),
+                    button => Some((ButtonType::XInputButton, vec![(*READABLE_TO_XINPUT.get(button)?).into()], vec![])),
+                }
+            }
+            _ => {}
+        }
+    }
+    match binding {
+        "profile-switch" => return Some((ButtonType::SwitchProfile, vec![], vec![])),
+        "disabled" => return Some((ButtonType::Disabled, vec![], vec![])),
+        "none" => return Some((ButtonType::KeyboardKey, vec![], vec![])),
+        _ => {}
+    }
+
+    let mut key_values = vec![];
+    let mut meta_keys = vec![];
+    for name in binding.split(" + ").map(str::trim) {
+        match META_KEYS.contains(&name) {
+            true => meta_keys.push(StringOrU32::String(name.to_string())),
+            false => key_values.push(js_key_code(name)?.into()),
+        }
+    }
+
+    Some((ButtonType::KeyboardKey, key_values, meta_keys))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ProfileFormat, ReadableProfile};
+    use crate::azeron::{button::ButtonType, unsorted::Profile};
+
+    #[test]
+    fn converts_profiles_without_losing_anything() {
+        let profile: Profile = serde_json::from_str(&std::fs::read_to_string("example-profile.json").unwrap()).unwrap();
+
+        for format in [ProfileFormat::Toml, ProfileFormat::Yaml] {
+            let converted = format.parse(&format.write(&profile).unwrap()).unwrap();
+            assert_eq!(serde_json::to_value(&converted).unwrap(), serde_json::to_value(&profile).unwrap());
+        }
+        let toml = ProfileFormat::Toml.write(&profile).unwrap();
+        assert!(toml.contains("1 = \"Space\""));
+        assert!(toml.contains("[buttons.HELLOOOO]"));
+    }
+
+    #[test]
+    fn reads_named_bindings() {
+        let readable: ReadableProfile = toml::from_str(
+            r#"
+            name = "racing"
+
+            [buttons]
+            15 = "CTRL + F"
+            22 = "xbox:A"
+            23 = "mouse:1"
+
+            [buttons.boost]
+            id = 24
+            bind = "SHIFT"
+            "#,
+        )
+        .unwrap();
+        let profile = readable.into_profile().unwrap();
+
+        let buttons = profile.buttons.iter().map(|b| (b.id, b.button_type, b.key_values.len(), b.user_label.as_str())).collect::<Vec<_>>();
+        assert_eq!(
+            buttons,
+            [(15, ButtonType::KeyboardKey, 1, ""), (22, ButtonType::XInputButton, 1, ""), (23, ButtonType::MouseButton, 1, ""), (24, ButtonType::KeyboardKey, 0, "boost")]
+        );
+        assert_eq!(profile.buttons[0].key_values[0].to_string(), "70");
+        assert_eq!(profile.buttons[1].key_values[0].to_string(), "4096");
+    }
+}
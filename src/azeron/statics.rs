@@ -1,8 +1,38 @@
 use std::collections::HashMap;
 
+use super::communication::FirmwareVersion;
+
 // haha funny statics go brrr
 // most of these are taken directly from the source of the official app
 
+pub const AZERON_VENDOR_ID: u16 = 0x16d0;
+pub const AZERON_PRODUCT_ID: u16 = 0x10bc;
+/// The interface the configuration protocol is spoken on, the others are the keyboard, mouse and joystick.
+pub const AZERON_INTERFACE: i32 = 4;
+
+/// The hardware profile slot software profiles are loaded into, the other slot is left alone.
+pub const SOFTWARE_PROFILE_SLOT: u32 = 1;
+pub const MAX_PROFILE_SLOT: u32 = 1;
+
+pub const MIN_FIRMWARE_VERSION: FirmwareVersion = FirmwareVersion { major: 60, minor: 0 };
+// The setting limits below haven't been confirmed with a capture of the official app yet, they are
+// what the replies seen so far suggest. Values past them are rejected before anything is sent.
+/// Not confirmed, `LEDBRT` has replied with values up to 5 so far.
+pub const MAX_LED_BRIGHTNESS: u8 = 10;
+/// Not confirmed, `BTNTHT` has replied with 10 so far.
+pub const MAX_BUTTON_THROTTLE: u8 = 100;
+/// Not confirmed, `GET_THROTTLE_TYPE` has replied with 0 and 1 so far.
+pub const MAX_THROTTLE_TYPE: u8 = 2;
+/// Half the range of a stick axis, which rests at 512 (see the joystick zones of the official app's profiles).
+pub const MAX_HARDWARE_DEADZONE: u16 = 512;
+/// Half the range of a stick axis, like `MAX_HARDWARE_DEADZONE`.
+pub const MAX_HARDWARE_ANALOG_OFFSET: i16 = 512;
+/// A button is sent with this many key values and meta keys, extra ones are cut off.
+pub const MAX_KEY_VALUES: usize = 4;
+pub const MAX_META_KEYS: usize = 3;
+/// The pins of the analog stick, shared by the buttons for its directions.
+pub const ANALOG_STICK_PINS: [u8; 2] = [39, 40];
+
 lazy_static::lazy_static! {
     pub static ref BUTTON_HAT_VALUES: [u16; 8] = [0, 45, 90, 135, 180, 225, 270, 315];
     pub static ref BUTTON_HAT_NAMES: HashMap<u16, &'static str> = {
@@ -516,6 +546,18 @@ lazy_static::lazy_static! {
             (38, [41, 255])
         ].into_iter().collect()
     };
+    /// The button at the mirrored position of the layout, to carry profiles between left- and right-handed keypads.
+    ///
+    /// Finger columns swap (pinky with index, ring with middle), as do the outer buttons beside them and the
+    /// thumb buttons either side of the stick. Buttons without a counterpart, the stick and the switch keep their IDs.
+    pub static ref MIRRORED_BUTTON_IDS: HashMap<u8, u8> = {
+        vec![
+            (4, 17), (3, 16), (2, 15), (1, 14), (37, 18),
+            (8, 12), (7, 11), (6, 10), (5, 9), (38, 13),
+            (36, 19),
+            (22, 31), (23, 20),
+        ].into_iter().flat_map(|(a, b)| [(a, b), (b, a)]).collect()
+    };
     pub static ref USB_TO_DEVICE_KEY: HashMap<u32, u32> = {
         vec![
             (0xe0, 57345),  // MODIFIERKEY_LEFT_CTRL
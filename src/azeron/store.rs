@@ -0,0 +1,166 @@
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+use super::{readable::ProfileFormat, unsorted::Profile, AzeronCliError};
+
+/// A directory of named profiles, one JSON file per profile.
+///
+/// The file name is the profile's name, so the directory can be kept in version control and edited by hand.
+pub struct ProfileStore {
+    dir: PathBuf,
+}
+
+impl ProfileStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// `$XDG_DATA_HOME/azeron-cli/profiles`, or `~/.local/share/azeron-cli/profiles` if it isn't set.
+    pub fn default_dir() -> PathBuf {
+        let data_home = match (env::var_os("XDG_DATA_HOME"), env::var_os("HOME")) {
+            (Some(dir), _) if !dir.is_empty() => PathBuf::from(dir),
+            (_, Some(home)) => PathBuf::from(home).join(".local/share"),
+            _ => env::temp_dir(),
+        };
+
+        data_home.join("azeron-cli").join("profiles")
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// All stored profiles, favourites first in the order they were favourited, then the rest by name.
+    ///
+    /// Files that can't be read as a profile are skipped with a warning, so one broken file doesn't hide the others.
+    pub fn list(&self) -> Result<Vec<Profile>, AzeronCliError> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut profiles = vec![];
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|e| e == "json") {
+                match read_profile(&path) {
+                    Ok(profile) => profiles.push(profile),
+                    Err(e) => eprintln!("warning: skipping {}: {}", path.display(), e),
+                }
+            }
+        }
+        profiles.sort_by(|a, b| {
+            let key = |p: &Profile| (!p.is_favourite, p.favourited_at.clone(), p.name.to_lowercase());
+            key(a).cmp(&key(b))
+        });
+
+        Ok(profiles)
+    }
+
+    pub fn load(&self, name: &str) -> Result<Profile, AzeronCliError> {
+        let path = self.path(name)?;
+        if !path.exists() {
+            return Err(AzeronCliError::ProfileNotFound { name: name.to_string() });
+        }
+
+        read_profile(&path)
+    }
+
+    /// Stores a profile under its name, replacing a stored profile with the same name.
+    pub fn save(&self, profile: &Profile) -> Result<(), AzeronCliError> {
+        let path = self.path(&profile.name)?;
+        fs::create_dir_all(&self.dir)?;
+        fs::write(path, ProfileFormat::Json.write(profile)?)?;
+
+        Ok(())
+    }
+
+    /// Stores a profile under a new name, failing if there is one already.
+    pub fn import(&self, mut profile: Profile, name: &str) -> Result<Profile, AzeronCliError> {
+        if self.path(name)?.exists() {
+            return Err(AzeronCliError::ProfileExists { name: name.to_string() });
+        }
+        profile.name = name.to_string();
+        self.save(&profile)?;
+
+        Ok(profile)
+    }
+
+    pub fn rename(&self, name: &str, new_name: &str) -> Result<(), AzeronCliError> {
+        let profile = self.load(name)?;
+        self.import(profile, new_name)?;
+
+        self.delete(name)
+    }
+
+    pub fn delete(&self, name: &str) -> Result<(), AzeronCliError> {
+        match fs::remove_file(self.path(name)?) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Err(AzeronCliError::ProfileNotFound { name: name.to_string() }),
+            result => Ok(result?),
+        }
+    }
+
+    /// Marks a profile as favourite (or not), which moves it to the top of the list.
+    pub fn set_favourite(&self, name: &str, favourite: bool) -> Result<(), AzeronCliError> {
+        let mut profile = self.load(name)?;
+        match (favourite, profile.is_favourite) {
+            // favouriting again would move it to the end of the favourites
+            (true, true) => return Ok(()),
+            (true, false) => profile.favourite(),
+            (false, _) => profile.unfavourite(),
+        }
+
+        self.save(&profile)
+    }
+
+    fn path(&self, name: &str) -> Result<PathBuf, AzeronCliError> {
+        if name.trim().is_empty() || name.starts_with('.') || name.contains(['/', '\\', '\0']) {
+            return Err(AzeronCliError::InvalidProfileName { name: name.to_string() });
+        }
+
+        Ok(self.dir.join(format!("{}.json", name)))
+    }
+}
+
+/// Reads a stored profile, its name is taken from the file name in case the file was renamed by hand.
+fn read_profile(path: &Path) -> Result<Profile, AzeronCliError> {
+    let mut profile: Profile = serde_json::from_str(&fs::read_to_string(path)?)?;
+    if let Some(name) = path.file_stem() {
+        profile.name = name.to_string_lossy().to_string();
+    }
+
+    Ok(profile)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::ProfileStore;
+    use crate::azeron::{unsorted::Profile, AzeronCliError};
+
+    #[test]
+    fn manages_named_profiles() {
+        let dir = std::env::temp_dir().join(format!("azeron-cli-store-{}", std::process::id()));
+        let store = ProfileStore::new(&dir);
+        for name in ["racing", "Flight", "shooter"] {
+            store.save(&Profile::new(0, name)).unwrap();
+        }
+        store.set_favourite("shooter", true).unwrap();
+        store.rename("racing", "driving").unwrap();
+        store.delete("Flight").unwrap();
+        fs::write(dir.join("broken.json"), "{").unwrap();
+
+        let names = store.list().unwrap().into_iter().map(|p| p.name).collect::<Vec<_>>();
+        let missing = store.load("Flight");
+        let invalid = store.save(&Profile::new(0, "../escape"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(names, ["shooter", "driving"]);
+        assert!(matches!(missing, Err(AzeronCliError::ProfileNotFound { .. })));
+        assert!(matches!(invalid, Err(AzeronCliError::InvalidProfileName { .. })));
+    }
+}
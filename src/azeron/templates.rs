@@ -0,0 +1,70 @@
+use super::{readable::ProfileFormat, unsorted::Profile, AzeronCliError};
+
+/// A built-in profile to start from, with a binding for every button.
+///
+/// Templates are readable profiles in TOML, kept in the `templates` directory of the repository.
+pub struct Template {
+    pub name: &'static str,
+    pub description: &'static str,
+    source: &'static str,
+}
+
+pub const TEMPLATES: &[Template] = &[
+    Template {
+        name: "mmo",
+        description: "WASD on the stick, a hotbar of numbers and SHIFT + numbers",
+        source: include_str!("../../templates/mmo.toml"),
+    },
+    Template {
+        name: "fps",
+        description: "WASD on the stick, actions around it",
+        source: include_str!("../../templates/fps.toml"),
+    },
+    Template {
+        name: "moba",
+        description: "abilities on the home row, items above, the stick pans the camera",
+        source: include_str!("../../templates/moba.toml"),
+    },
+    Template {
+        name: "gamepad",
+        description: "an Xbox controller for emulators, with hotkeys on the spare buttons",
+        source: include_str!("../../templates/gamepad.toml"),
+    },
+];
+
+impl Template {
+    pub fn find(name: &str) -> Result<&'static Template, AzeronCliError> {
+        TEMPLATES.iter().find(|t| t.name == name).ok_or_else(|| AzeronCliError::UnknownTemplate {
+            name: name.to_string(),
+            templates: TEMPLATES.iter().map(|t| t.name).collect::<Vec<_>>().join(", "),
+        })
+    }
+
+    /// A new software profile called `name` with the bindings of this template.
+    pub fn instantiate(&self, name: &str) -> Result<Profile, AzeronCliError> {
+        let mut profile = ProfileFormat::Toml.parse(self.source)?;
+        profile.name = name.to_string();
+
+        Ok(profile)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TEMPLATES;
+    use crate::azeron::{lint::check_profile, statics::BUTTON_IDS_TO_PINS};
+
+    #[test]
+    fn templates_bind_every_button() {
+        for template in TEMPLATES {
+            let profile = template.instantiate("new").unwrap();
+            let mut ids = profile.buttons.iter().map(|b| b.id).collect::<Vec<_>>();
+            let mut expected = BUTTON_IDS_TO_PINS.keys().copied().collect::<Vec<_>>();
+            ids.sort_unstable();
+            expected.sort_unstable();
+
+            assert_eq!(ids, expected, "{}", template.name);
+            assert_eq!(check_profile(&profile), [], "{}", template.name);
+        }
+    }
+}
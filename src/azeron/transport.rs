@@ -0,0 +1,322 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+use chrono::{SecondsFormat, Utc};
+#[cfg(feature = "hid")]
+use hidapi::HidDevice;
+use serde::{Deserialize, Serialize};
+
+use super::AzeronCliError;
+
+/// Something reports can be written to and read from, usually the HID device itself.
+pub trait Transport {
+    fn write(&self, data: &[u8]) -> Result<usize, AzeronCliError>;
+    /// Reads a single report, returning 0 if none arrived within `timeout` milliseconds.
+    fn read_timeout(&self, buf: &mut [u8], timeout: i32) -> Result<usize, AzeronCliError>;
+}
+
+#[cfg(feature = "hid")]
+impl Transport for HidDevice {
+    fn write(&self, data: &[u8]) -> Result<usize, AzeronCliError> {
+        Ok(HidDevice::write(self, data)?)
+    }
+
+    fn read_timeout(&self, buf: &mut [u8], timeout: i32) -> Result<usize, AzeronCliError> {
+        Ok(HidDevice::read_timeout(self, buf, timeout)?)
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for Box<T> {
+    fn write(&self, data: &[u8]) -> Result<usize, AzeronCliError> {
+        (**self).write(data)
+    }
+
+    fn read_timeout(&self, buf: &mut [u8], timeout: i32) -> Result<usize, AzeronCliError> {
+        (**self).read_timeout(buf, timeout)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum TraceDirection {
+    Out,
+    In,
+}
+
+/// A single line of a trace file: one frame sent to or one report received from the device.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TraceEntry {
+    pub timestamp: String,
+    pub direction: TraceDirection,
+    /// The raw bytes as lowercase hex.
+    pub data: String,
+}
+
+/// Records every frame written and every report read through the wrapped transport as JSON lines.
+pub struct TraceRecorder<T> {
+    inner: T,
+    output: Mutex<Box<dyn Write + Send>>,
+}
+
+impl<T: Transport> TraceRecorder<T> {
+    pub fn new(inner: T, output: impl Write + Send + 'static) -> Self {
+        Self {
+            inner,
+            output: Mutex::new(Box::new(output)),
+        }
+    }
+
+    pub fn create(inner: T, path: impl AsRef<Path>) -> Result<Self, AzeronCliError> {
+        Ok(Self::new(inner, File::create(path)?))
+    }
+
+    fn record(&self, direction: TraceDirection, data: &[u8]) -> Result<(), AzeronCliError> {
+        let entry = TraceEntry {
+            timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true),
+            direction,
+            data: to_hex(data),
+        };
+        let mut output = self.output.lock().unwrap_or_else(|e| e.into_inner());
+        writeln!(output, "{}", serde_json::to_string(&entry)?)?;
+        output.flush()?;
+
+        Ok(())
+    }
+}
+
+impl<T: Transport> Transport for TraceRecorder<T> {
+    fn write(&self, data: &[u8]) -> Result<usize, AzeronCliError> {
+        self.record(TraceDirection::Out, data)?;
+        self.inner.write(data)
+    }
+
+    fn read_timeout(&self, buf: &mut [u8], timeout: i32) -> Result<usize, AzeronCliError> {
+        let read = self.inner.read_timeout(buf, timeout)?;
+        if read > 0 {
+            self.record(TraceDirection::In, &buf[..read])?;
+        }
+
+        Ok(read)
+    }
+}
+
+/// Plays a recorded trace back as if it was the device.
+///
+/// Written frames have to match the recorded ones in order, reads return the recorded reports
+/// and time out as soon as the trace expects the next frame to be written.
+pub struct ReplayTransport {
+    entries: Mutex<VecDeque<(TraceDirection, Vec<u8>)>>,
+}
+
+impl ReplayTransport {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, AzeronCliError> {
+        Self::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    pub fn from_reader(reader: impl BufRead) -> Result<Self, AzeronCliError> {
+        let mut entries = VecDeque::new();
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry: TraceEntry = serde_json::from_str(&line)?;
+            entries.push_back((entry.direction, from_hex(&entry.data)?));
+        }
+
+        Ok(Self { entries: Mutex::new(entries) })
+    }
+
+    /// Whether every recorded entry has been played back.
+    pub fn is_finished(&self) -> bool {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).is_empty()
+    }
+}
+
+impl Transport for ReplayTransport {
+    fn write(&self, data: &[u8]) -> Result<usize, AzeronCliError> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        match entries.pop_front() {
+            Some((TraceDirection::Out, expected)) if expected == data => Ok(data.len()),
+            Some((_, expected)) => Err(AzeronCliError::ReplayMismatch { expected: to_hex(&expected), actual: to_hex(data) }),
+            None => Err(AzeronCliError::ReplayMismatch { expected: "end of trace".to_string(), actual: to_hex(data) }),
+        }
+    }
+
+    fn read_timeout(&self, buf: &mut [u8], _timeout: i32) -> Result<usize, AzeronCliError> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if !matches!(entries.front(), Some((TraceDirection::In, _))) {
+            return Ok(0);
+        }
+        let (_, report) = entries.pop_front().unwrap();
+        let read = report.len().min(buf.len());
+        buf[..read].copy_from_slice(&report[..read]);
+
+        Ok(read)
+    }
+}
+
+fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Result<Vec<u8>, AzeronCliError> {
+    // a shorter chunk at the end means the length is odd
+    hex.as_bytes()
+        .chunks(2)
+        .map(|pair| match pair {
+            [high, low] => (*high as char).to_digit(16).zip((*low as char).to_digit(16)).map(|(high, low)| (high * 16 + low) as u8),
+            _ => None,
+        })
+        .map(|byte| byte.ok_or_else(|| AzeronCliError::MalformedTrace { data: hex.to_string() }))
+        .collect()
+}
+
+/// Traces for tests that talk to a pretend device.
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use super::{to_hex, ReplayTransport};
+
+    /// A report as the device sends it, padded with zeros to 64 bytes.
+    pub fn report(text: &[u8]) -> Vec<u8> {
+        let mut report = text.to_vec();
+        report.resize(64, 0);
+        report
+    }
+
+    /// Replays `("out", frame)` and `("in", report)` entries in order.
+    pub fn trace(entries: &[(&str, Vec<u8>)]) -> ReplayTransport {
+        let lines = entries
+            .iter()
+            .map(|(direction, data)| format!(r#"{{"timestamp":"2022-07-20T12:00:00.000000Z","direction":"{}","data":"{}"}}"#, direction, to_hex(data)))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        ReplayTransport::from_reader(lines.as_bytes()).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fixtures::{report, trace};
+    use crate::azeron::{
+        button::{Button, ButtonType},
+        communication::{ReplyOptions, RequestMessage, ResponseMessage},
+        frame::{to_azeron_message, Reply},
+        AzeronCliError,
+    };
+
+    #[test]
+    fn replays_set_button() {
+        let request = RequestMessage::SetButton {
+            profile_id: 0,
+            button: Button::new(15, ButtonType::KeyboardKey, [5, 255], vec![61449.into()], vec![]),
+            is_js_keycode: false,
+        };
+        let replay = trace(&[
+            ("out", to_azeron_message("B0|15|1|5|255|61449|0|0|0|0|0|0|0").unwrap()),
+            ("in", report(b"BOK_15")),
+        ]);
+
+        let response = request.send_message(&replay, &ReplyOptions::default()).unwrap();
+
+        assert!(matches!(response, ResponseMessage::SetButtonResponse(true)));
+        assert!(replay.is_finished());
+    }
+
+    #[test]
+    fn skips_input_reports_while_waiting_for_reply() {
+        let mut input_report = vec![0u8; 64];
+        input_report[4] = 1;
+        let replay = trace(&[
+            ("out", to_azeron_message("LEDBRT").unwrap()),
+            ("in", input_report),
+            ("in", report(b"LEDBRT_5")),
+        ]);
+
+        let response = RequestMessage::GetLEDBrightness.send_message(&replay, &ReplyOptions::default()).unwrap();
+
+        assert!(matches!(response, ResponseMessage::Reply(Reply::LEDBrightness(5))));
+    }
+
+    #[test]
+    fn skips_input_reports_that_start_like_text() {
+        let mut input_report = vec![0u8; 64];
+        input_report[0] = b'L';
+        input_report[4] = 1;
+        let replay = trace(&[
+            ("out", to_azeron_message("LEDBRT").unwrap()),
+            ("in", input_report),
+            ("in", report(b"LEDBRT_5")),
+        ]);
+
+        let response = RequestMessage::GetLEDBrightness.send_message(&replay, &ReplyOptions::default()).unwrap();
+
+        assert!(matches!(response, ResponseMessage::Reply(Reply::LEDBrightness(5))));
+    }
+
+    #[test]
+    fn skips_input_reports_in_the_middle_of_a_frame() {
+        let mut input_report = vec![0u8; 64];
+        input_report[4] = 1;
+        let replay = trace(&[
+            ("out", to_azeron_message("LEDBRT").unwrap()),
+            ("in", report(b"\0^8~LEDB")),
+            ("in", input_report),
+            ("in", report(b"RT_5\n")),
+        ]);
+
+        let response = RequestMessage::GetLEDBrightness.send_message(&replay, &ReplyOptions::default()).unwrap();
+
+        assert!(matches!(response, ResponseMessage::Reply(Reply::LEDBrightness(5))));
+        assert!(replay.is_finished());
+    }
+
+    #[test]
+    fn skips_reports_that_cant_be_decoded() {
+        let replay = trace(&[
+            ("out", to_azeron_message("LEDBRT").unwrap()),
+            ("in", report(b"^x~LEDBRT_3\n")),
+            ("in", report(b"^8~LEDBRT_5\n")),
+        ]);
+
+        let response = RequestMessage::GetLEDBrightness.send_message(&replay, &ReplyOptions::default()).unwrap();
+
+        assert!(matches!(response, ResponseMessage::Reply(Reply::LEDBrightness(5))));
+    }
+
+    #[test]
+    fn retries_after_timeout() {
+        let replay = trace(&[
+            ("out", to_azeron_message("GET_LEDS").unwrap()),
+            ("out", to_azeron_message("GET_LEDS").unwrap()),
+            ("in", report(b"LEDS_ON")),
+        ]);
+
+        let response = RequestMessage::GetLEDState.send_message(&replay, &ReplyOptions::default()).unwrap();
+
+        assert!(matches!(response, ResponseMessage::Reply(Reply::LEDState(true))));
+    }
+
+    #[test]
+    fn rejects_unexpected_frames() {
+        let replay = trace(&[("out", to_azeron_message("GET_LEDS").unwrap())]);
+
+        let result = RequestMessage::GetLEDBrightness.send_message(&replay, &ReplyOptions::default());
+
+        assert!(matches!(result, Err(AzeronCliError::ReplayMismatch { .. })));
+    }
+
+    #[test]
+    fn decodes_hex_data() {
+        assert_eq!(super::from_hex("005eff").unwrap(), [0x00, 0x5e, 0xff]);
+        for data in ["005", "0g", "+f", "é0"] {
+            assert!(matches!(super::from_hex(data), Err(AzeronCliError::MalformedTrace { .. })), "{}", data);
+        }
+    }
+}
@@ -0,0 +1,18 @@
+//! Library for managing Azeron keypads on any operating system.
+//!
+//! [`Azeron`] is the entry point: it opens a keypad and reads or changes its settings and profiles.
+//! The building blocks it uses are available in [`azeron`] as well, e.g. the profile model,
+//! the key code tables and the wire protocol.
+//!
+//! Opening devices needs the `hid` feature (on by default). Without it, the crate builds without
+//! the native HID libraries and everything else works over any [`azeron::transport::Transport`].
+
+pub mod azeron;
+
+pub use azeron::{
+    client::Azeron,
+    communication::{AnalogType, FirmwareVersion, ReplyOptions, RequestMessage, ResponseMessage},
+    input::InputReport,
+    unsorted::Profile,
+    AzeronCliError,
+};
@@ -1,19 +1,81 @@
-use azeron::{
-    button::{Button, ButtonType},
-    unsorted::StringOrU32, statics::BUTTON_IDS_TO_PINS,
+use azeron_cli::{
+    azeron::{
+        self,
+        button::{Button, ButtonType},
+        client::profile_switch_button,
+        diagram::render_diagram,
+        discovery::{azeron_devices, list_devices, open_device},
+        lint::check_profile,
+        readable::{read_profile_file, write_profile_file, ProfileFormat},
+        store::ProfileStore,
+        templates::{Template, TEMPLATES},
+        unsorted::{Profile, StringOrU32}, statics::{BUTTON_IDS_TO_PINS, MAX_PROFILE_SLOT, SOFTWARE_PROFILE_SLOT},
+        transport::{ReplayTransport, TraceRecorder, Transport},
+    },
+    AnalogType, Azeron, AzeronCliError, ReplyOptions, RequestMessage, ResponseMessage,
+    azeron::frame::Reply,
 };
-use clap::{Parser, Subcommand};
+#[cfg(unix)]
+use azeron_cli::azeron::{
+    client::parse_profiles,
+    daemon::{bind, default_socket_path, serve, AutoSwitch, DaemonClient, DaemonOptions},
+    focus::{AutoSwitcher, CommandSource, FocusSource, ProcSource, SharedFocus, SwitchRule},
+};
+use clap::{Parser, Subcommand, ValueEnum};
+use script::read_script;
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+#[cfg(unix)]
+use std::sync::{atomic::AtomicBool, Arc};
 
-mod azeron;
+mod script;
 
 #[derive(Parser)]
 struct CliArgs {
+    /// How long to wait for a reply from the device, in milliseconds.
+    #[clap(long, global = true, default_value_t = 1000)]
+    timeout: u64,
+    /// How often to resend a request the device didn't reply to.
+    #[clap(long, global = true, default_value_t = 2)]
+    retries: u32,
+    /// The serial number or path of the keypad to use, if more than one is connected.
+    #[clap(long, global = true)]
+    device: Option<String>,
+    /// Prints the frames that would be sent instead of opening the device.
+    #[clap(long, global = true)]
+    dry_run: bool,
+    /// Records every frame sent to and every report received from the device into this file.
+    #[clap(long, global = true)]
+    trace: Option<PathBuf>,
+    /// Plays a file recorded with `--trace` back instead of talking to a real device.
+    #[clap(long, global = true, conflicts_with = "trace")]
+    replay: Option<PathBuf>,
+    /// The socket of the daemon, defaults to `$XDG_RUNTIME_DIR/azeron-cli.sock`.
+    #[cfg(unix)]
+    #[clap(long, global = true)]
+    socket: Option<PathBuf>,
     #[clap(subcommand)]
     pub subcommand: CliAction,
 }
 
 #[derive(Subcommand)]
 enum CliAction {
+    /// Lists all connected Azeron keypads.
+    Devices,
+    /// Shows which profile is active.
+    Status,
+    /// Checks why the Azeron can't be used and explains how to fix it.
+    Doctor {
+        /// Prints a udev rule that gives the logged in user access to the keypad.
+        #[clap(long)]
+        print_udev_rule: bool,
+        /// Installs the udev rule (needs root).
+        #[clap(long, conflicts_with = "print-udev-rule")]
+        install_udev_rule: bool,
+    },
     /// Sets a button of the Azeron to the desired keys.
     SetButton {
         /// The profile to set the button in (0 or 1, other values will not work).
@@ -25,39 +87,958 @@ enum CliAction {
         meta_keys: Vec<String>,
         /// Key to press, using the Azeron key codes
         key_value: u32,
+    },
+    /// Changes the LEDs of the Azeron.
+    Led {
+        #[clap(subcommand)]
+        action: LedAction,
+    },
+    /// Sets the shape the analog stick is mapped to.
+    AnalogType {
+        #[clap(value_enum)]
+        analog_type: AnalogTypeArg,
+    },
+    /// Sets the hardware deadzones of the analog stick.
+    Deadzone {
+        #[clap(subcommand)]
+        action: DeadzoneAction,
+    },
+    /// Sets the hardware offset of the analog stick.
+    AnalogOffset {
+        /// Offset on the X axis.
+        #[clap(allow_hyphen_values = true)]
+        x: i16,
+        /// Offset on the Y axis.
+        #[clap(allow_hyphen_values = true)]
+        y: i16,
+    },
+    /// Sets the button throttle of the Azeron.
+    Throttle {
+        /// The button throttle to set (0-100).
+        value: u8,
+        /// The throttle type to set as well (0-2).
+        #[clap(short = 't', long = "type")]
+        throttle_type: Option<u8>,
+    },
+    /// Sends a raw command to the Azeron and prints the reports it sends back.
+    ///
+    /// Waits until the given number of reports arrived or `--timeout` passed.
+    Raw {
+        /// The command to send, without framing (e.g. `GET_FW_VERSION`).
+        command: String,
+        /// How many reports to wait for, 0 waits for the whole timeout.
+        #[clap(short = 'n', long, default_value_t = 1)]
+        replies: usize,
+        /// How to print the reports.
+        #[clap(short, long, value_enum, default_value_t = RawFormat::Both)]
+        format: RawFormat,
+    },
+    /// Keeps the Azeron open in the background and lets other commands use it through a socket.
+    ///
+    /// While the daemon runs, commands are sent through it instead of opening the device themselves.
+    #[cfg(unix)]
+    Daemon {
+        /// How often to send the keep alive message, in seconds.
+        #[clap(long, default_value_t = 5)]
+        keep_alive: u64,
+        /// The profile slot software profiles are loaded into (0 or 1).
+        #[clap(long, default_value_t = SOFTWARE_PROFILE_SLOT)]
+        software_slot: u32,
+        /// Switches profiles while an application is in use, as `APPLICATION=PROFILE`.
+        ///
+        /// PROFILE is a profile from the library, or a hardware slot (0 or 1). Can be given multiple times, the first matching rule wins.
+        #[clap(long = "rule")]
+        rules: Vec<SwitchRule>,
+        /// How to find out which application is in use.
+        #[clap(long, value_enum, default_value_t = FocusSourceArg::Focused)]
+        focus_source: FocusSourceArg,
+        /// The shell command that prints the focused application, for `--focus-source focused`.
+        #[clap(long)]
+        focus_command: Option<String>,
+        /// How often to check which application is in use, in milliseconds.
+        #[clap(long, default_value_t = 1000)]
+        focus_interval: u64,
+        /// The profile library the rules refer to, defaults to `$XDG_DATA_HOME/azeron-cli/profiles`.
+        #[clap(long)]
+        profile_dir: Option<PathBuf>,
+    },
+    /// Tells the daemon which application is focused, for `daemon --focus-source socket`.
+    #[cfg(unix)]
+    Focus {
+        /// The process name of the focused application, leave out if nothing is focused.
+        application: Option<String>,
+    },
+    /// Manages profiles.
+    Profile {
+        /// The profile library, defaults to `$XDG_DATA_HOME/azeron-cli/profiles`.
+        #[clap(long)]
+        dir: Option<PathBuf>,
+        #[clap(subcommand)]
+        action: ProfileAction,
+    },
+    /// Runs the commands of a script, one per line, over a single connection to the Azeron.
+    Run {
+        /// The script to run, `-` reads it from stdin.
+        script: PathBuf,
+        /// Runs the remaining commands after one failed instead of stopping.
+        #[clap(short, long)]
+        keep_going: bool,
+    },
+}
+
+#[cfg(unix)]
+#[derive(ValueEnum, Clone, Copy)]
+enum FocusSourceArg {
+    /// The focused window, asked for with `--focus-command` (X11 with `xdotool` by default).
+    Focused,
+    /// Any running process, read from `/proc`.
+    Proc,
+    /// Whatever was last sent with `azeron-cli focus`.
+    Socket,
+}
+
+#[derive(ValueEnum, Clone, Copy, PartialEq)]
+enum RawFormat {
+    Text,
+    Hex,
+    Both,
+}
+
+/// A single line of a script run with `azeron-cli run`.
+#[derive(Parser)]
+#[clap(no_binary_name = true)]
+struct ScriptArgs {
+    #[clap(subcommand)]
+    subcommand: CliAction,
+}
+
+#[derive(Subcommand)]
+enum LedAction {
+    /// Turns the LEDs on.
+    On,
+    /// Turns the LEDs off.
+    Off,
+    /// Sets the LED brightness (0-10).
+    Brightness {
+        brightness: u8,
+    },
+}
+
+#[derive(Subcommand)]
+enum ProfileAction {
+    /// Loads a software profile from a file into the scratch slot of the Azeron.
+    ///
+    /// Software profiles only live on this computer. With the daemon running, its `--software-slot` is used.
+    Load {
+        /// The profile file, as exported by the official app or in TOML or YAML.
+        path: PathBuf,
+    },
+    /// Lists the profiles in the library, favourites first.
+    List,
+    /// Shows a profile from the library.
+    Show {
+        name: String,
+        /// Draws the keypad with the label or keys of every button instead of listing them.
+        #[clap(long)]
+        diagram: bool,
+    },
+    /// Copies a profile file into the library.
+    Import {
+        /// The profile file, as exported by the official app or in TOML or YAML.
+        path: PathBuf,
+        /// The name to store it under, defaults to the name in the file.
+        #[clap(long)]
+        name: Option<String>,
+    },
+    /// Writes a profile from the library to the Azeron.
+    ///
+    /// Without `--slot`, software profiles are loaded into the scratch slot.
+    Activate {
+        name: String,
+        /// The profile slot to write to (0 or 1).
+        #[clap(long)]
+        slot: Option<u32>,
+    },
+    /// Switches the Azeron to one of its hardware profiles.
+    Switch {
+        /// The profile slot to switch to (0 or 1).
+        slot: u32,
+    },
+    /// Prints the profiles stored on the Azeron as JSON, or writes them to a file.
+    Export {
+        /// Writes the profiles to this file instead of printing them.
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Makes a button switch between the hardware profiles.
+    BindSwitch {
+        /// The button ID to bind (1-38).
+        button_id: u8,
+        /// The profile slot to bind it in, defaults to both so the button switches back and forth.
+        #[clap(long)]
+        slot: Option<u32>,
+    },
+    /// Creates a new software profile in the library, from a template or without any buttons.
+    New {
+        name: String,
+        /// The built-in template to start from, see `profile templates`.
+        #[clap(short, long)]
+        template: Option<String>,
+        /// Writes the profile to this file instead of the library, e.g. a `.toml` file to edit.
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Lists the built-in templates for `profile new`.
+    Templates,
+    /// Converts a profile file to another format, picked by the file extensions.
+    ///
+    /// `.toml` and `.yaml` files bind buttons by key names (e.g. `CTRL + F`, `xbox:A`), anything else is JSON as used by the official app.
+    Convert {
+        input: PathBuf,
+        output: PathBuf,
+    },
+    /// Writes some buttons of a profile file to the Azeron, leaving the others as they are.
+    Apply {
+        /// The profile file, as exported by the official app or in TOML or YAML.
+        path: PathBuf,
+        /// The button IDs to write, like `1-9,22`, defaults to every button in the file.
+        #[clap(long)]
+        buttons: Option<ButtonIds>,
+        /// The profile slot to write to, defaults to the scratch slot for software profiles.
+        #[clap(long, default_value_t = SOFTWARE_PROFILE_SLOT)]
+        slot: u32,
+    },
+    /// Layers the buttons of one profile file over another, e.g. a shared movement layout over a game profile.
+    Merge {
+        base: PathBuf,
+        overlay: PathBuf,
+        /// The button IDs to take from the overlay, like `1-9,22`, defaults to every button in it.
+        #[clap(long)]
+        buttons: Option<ButtonIds>,
+        /// Writes the merged profile to this file instead of printing it as JSON.
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Mirrors a profile file for the keypad of the other hand, moving every button to its mirrored position.
+    Mirror {
+        input: PathBuf,
+        output: PathBuf,
+    },
+    /// Checks a profile file for problems before it is written to the Azeron, exits with 2 if there are any.
+    Check {
+        path: PathBuf,
+    },
+    /// Renames a profile in the library.
+    Rename {
+        name: String,
+        new_name: String,
+    },
+    /// Deletes a profile from the library.
+    Delete {
+        name: String,
+    },
+    /// Marks a profile as favourite, favourites are listed first.
+    Favourite {
+        name: String,
+    },
+    /// Removes the favourite mark from a profile.
+    Unfavourite {
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum DeadzoneAction {
+    /// Sets the lower hardware deadzone (0-512).
+    Lower {
+        deadzone: u16,
+    },
+    /// Sets the upper hardware deadzone (0-512).
+    Upper {
+        deadzone: u16,
+    },
+}
+
+#[derive(ValueEnum, Clone)]
+enum AnalogTypeArg {
+    Square,
+    Circle,
+}
+
+impl From<AnalogTypeArg> for AnalogType {
+    fn from(analog_type: AnalogTypeArg) -> Self {
+        match analog_type {
+            AnalogTypeArg::Square => AnalogType::Square,
+            AnalogTypeArg::Circle => AnalogType::Circle,
+        }
+    }
+}
+
+/// A list of button IDs and ranges of them, like `1-9,22`.
+#[derive(Clone)]
+struct ButtonIds(Vec<u8>);
+
+impl FromStr for ButtonIds {
+    type Err = String;
+
+    fn from_str(list: &str) -> Result<Self, String> {
+        let mut button_ids = vec![];
+        for part in list.split(',') {
+            let parse = |id: &str| id.trim().parse::<u8>().map_err(|_| format!("expected a button ID or a range like 1-9, got {}", part));
+            let (first, last) = match part.split_once('-') {
+                Some((first, last)) => (parse(first)?, parse(last)?),
+                None => (parse(part)?, parse(part)?),
+            };
+            if first > last {
+                return Err(format!("the range {} is backwards", part));
+            }
+            for button_id in first..=last {
+                if !BUTTON_IDS_TO_PINS.contains_key(&button_id) {
+                    return Err(format!("there is no button with ID {}", button_id));
+                }
+                if !button_ids.contains(&button_id) {
+                    button_ids.push(button_id);
+                }
+            }
+        }
+
+        Ok(Self(button_ids))
     }
 }
 
 fn main() {
     let args = CliArgs::parse();
 
-    let api = hidapi::HidApi::new().expect("Failed to create HID API");
-    let azeron = api
-        .device_list()
-        .find(|d| d.vendor_id() == 0x16d0 && d.product_id() == 0x10bc && d.interface_number() == 4)
-        .expect("Failed to find Azeron device");
-        let device = azeron.open_device(&api).expect("Failed to open Azeron device for communication");
-    
+    if let Err(e) = run(args) {
+        eprintln!("error: {}", e);
+        std::process::exit(e.exit_code());
+    }
+}
+
+fn run(args: CliArgs) -> Result<(), AzeronCliError> {
+    let mut session = Session {
+        options: ReplyOptions {
+            timeout: Duration::from_millis(args.timeout),
+            retries: args.retries,
+        },
+        dry_run: args.dry_run,
+        selector: args.device,
+        trace: args.trace,
+        replay: args.replay,
+        #[cfg(unix)]
+        socket: args.socket.unwrap_or_else(default_socket_path),
+        device: None,
+    };
+
     match args.subcommand {
-        CliAction::SetButton { profile_id, button_id, key_value, meta_keys } => {
-            let response = azeron::communication::RequestMessage::SetButton {
-                profile_id,
-                button: Button::new(
-                    button_id,
-                    ButtonType::KeyboardKey,
-                    *BUTTON_IDS_TO_PINS.get(&button_id).expect("invalid button id"),
-                    vec![key_value.into()],
-                    meta_keys
-                        .iter()
-                        .map(|k| StringOrU32::String(k.to_string()))
-                        .collect::<Vec<StringOrU32>>(),
-                ),
-                is_js_keycode: false,
-            }
-            .send_message(&device)
-            .expect("Failed to send message");
-
-            println!("success: {}", response);
+        CliAction::Devices => {
+            let api = hidapi::HidApi::new()?;
+            let devices = list_devices(&api, &session.options);
+            if devices.is_empty() {
+                return Err(AzeronCliError::DeviceNotFound);
+            }
+            for device in devices {
+                println!("{}", device.serial_number.as_deref().unwrap_or("(no serial number)"));
+                println!("  path:     {}", device.path);
+                println!("  model:    {}", device.model.as_deref().unwrap_or("unknown"));
+                match device.firmware {
+                    Ok((version, keypad_type)) => {
+                        let keypad_type = keypad_type.iter().map(|t| t.to_string()).collect::<Vec<_>>().join(".");
+                        println!("  firmware: {} (keypad type {})", version, keypad_type);
+                    }
+                    Err(e) => println!("  firmware: unknown ({})", e),
+                }
+            }
+            Ok(())
+        }
+        CliAction::Doctor { print_udev_rule, install_udev_rule } => doctor(print_udev_rule, install_udev_rule),
+        #[cfg(unix)]
+        CliAction::Daemon { keep_alive, software_slot, rules, focus_source, focus_command, focus_interval, profile_dir } => {
+            // every switch ends with `SET_PROFILE`, which isn't sent until it is confirmed
+            let activate = RequestMessage::SetActiveProfile { profile_id: software_slot };
+            if !rules.is_empty() && activate.is_unconfirmed() {
+                return Err(AzeronCliError::UnconfirmedCommand { command: activate.command()? });
+            }
+            let focus = SharedFocus::default();
+            let auto_switch = match rules.is_empty() {
+                true => None,
+                false => Some(AutoSwitch {
+                    source: match focus_source {
+                        FocusSourceArg::Focused => Box::new(focus_command.map(CommandSource::new).unwrap_or_default()) as Box<dyn FocusSource>,
+                        FocusSourceArg::Proc => Box::new(ProcSource::default()),
+                        FocusSourceArg::Socket => Box::new(focus.clone()),
+                    },
+                    switcher: AutoSwitcher::new(rules),
+                    store: ProfileStore::new(profile_dir.unwrap_or_else(ProfileStore::default_dir)),
+                    interval: Duration::from_millis(focus_interval),
+                }),
+            };
+            let options = DaemonOptions { reply: session.options, keep_alive: Duration::from_secs(keep_alive), software_slot, focus, auto_switch };
+            daemon(session, options)
+        }
+        CliAction::Run { script, keep_going } => run_script(&script, keep_going, &mut session),
+        action => execute(action, &mut session),
+    }
+}
+
+/// The device all commands of an invocation are sent to, opened on first use.
+struct Session {
+    options: ReplyOptions,
+    dry_run: bool,
+    selector: Option<String>,
+    trace: Option<PathBuf>,
+    replay: Option<PathBuf>,
+    #[cfg(unix)]
+    socket: PathBuf,
+    device: Option<Device>,
+}
+
+/// Either the keypad itself, or a daemon that holds it.
+enum Device {
+    Direct(Azeron<Box<dyn Transport + Send>>),
+    #[cfg(unix)]
+    Daemon(DaemonClient),
+}
+
+impl Device {
+    fn send(&self, request: &RequestMessage) -> Result<ResponseMessage, AzeronCliError> {
+        match self {
+            Device::Direct(azeron) => azeron.send(request),
+            #[cfg(unix)]
+            Device::Daemon(client) => client.send(request),
+        }
+    }
+
+    fn profiles(&self) -> Result<Vec<Profile>, AzeronCliError> {
+        match self {
+            Device::Direct(azeron) => azeron.profiles(),
+            #[cfg(unix)]
+            Device::Daemon(client) => match client.send(&RequestMessage::GetProfiles)? {
+                ResponseMessage::Reply(reply) => parse_profiles(reply),
+                response => Err(AzeronCliError::MalformedResponse { response: response.to_string() }),
+            },
+        }
+    }
+
+    fn load_software_profile(&self, profile: &Profile) -> Result<(), AzeronCliError> {
+        match self {
+            Device::Direct(azeron) => azeron.load_software_profile(profile, SOFTWARE_PROFILE_SLOT),
+            #[cfg(unix)]
+            Device::Daemon(client) => client.load_software_profile(profile),
+        }
+    }
+}
+
+impl Session {
+    /// Connects to the daemon if one is running, unless a specific device, trace or replay was asked for.
+    fn device(&mut self) -> Result<&Device, AzeronCliError> {
+        if self.device.is_none() {
+            let device = match self.daemon() {
+                Some(device) => device,
+                None => {
+                    let transport = open_transport(self.selector.as_deref(), self.replay.take(), self.trace.take())?;
+                    let azeron = Azeron::new(transport).with_options(self.options);
+                    azeron.firmware_version()?.ensure_supported()?;
+                    Device::Direct(azeron)
+                }
+            };
+            self.device = Some(device);
+        }
+
+        Ok(self.device.as_ref().unwrap())
+    }
+
+    #[cfg(unix)]
+    fn daemon(&self) -> Option<Device> {
+        match (&self.selector, &self.trace, &self.replay) {
+            (None, None, None) => DaemonClient::connect(&self.socket).ok().map(Device::Daemon),
+            _ => None,
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn daemon(&self) -> Option<Device> {
+        None
+    }
+}
+
+/// Runs a single device command, or prints the frames it would send with `--dry-run`.
+fn execute(action: CliAction, session: &mut Session) -> Result<(), AzeronCliError> {
+    let requests = match action {
+        CliAction::SetButton { profile_id, button_id, key_value, meta_keys } => vec![RequestMessage::SetButton {
+            profile_id,
+            button: Button::new(
+                button_id,
+                ButtonType::KeyboardKey,
+                *BUTTON_IDS_TO_PINS.get(&button_id).ok_or(AzeronCliError::InvalidButtonId { button_id })?,
+                vec![key_value.into()],
+                meta_keys
+                    .iter()
+                    .map(|k| StringOrU32::String(k.to_string()))
+                    .collect::<Vec<StringOrU32>>(),
+            ),
+            is_js_keycode: false,
+        }],
+        CliAction::Led { action } => match action {
+            LedAction::On => vec![RequestMessage::SetLEDState { enabled: true }],
+            LedAction::Off => vec![RequestMessage::SetLEDState { enabled: false }],
+            LedAction::Brightness { brightness } => vec![RequestMessage::SetLEDBrightness { brightness }],
+        },
+        CliAction::AnalogType { analog_type } => vec![RequestMessage::SetAnalogType { analog_type: analog_type.into() }],
+        CliAction::Deadzone { action } => match action {
+            DeadzoneAction::Lower { deadzone } => vec![RequestMessage::SetHardwareLowerDeadzone { deadzone }],
+            DeadzoneAction::Upper { deadzone } => vec![RequestMessage::SetHardwareUpperDeadzone { deadzone }],
+        },
+        CliAction::AnalogOffset { x, y } => vec![RequestMessage::SetHardwareAnalogOffset { x, y }],
+        CliAction::Throttle { value, throttle_type } => {
+            let mut requests = vec![RequestMessage::SetButtonThrottle { throttle: value }];
+            if let Some(throttle_type) = throttle_type {
+                requests.push(RequestMessage::SetThrottleType { throttle_type });
+            }
+            requests
+        }
+        CliAction::Profile { dir, action } => {
+            let store = ProfileStore::new(dir.unwrap_or_else(ProfileStore::default_dir));
+            let (profile, slot): (Profile, _) = match action {
+                ProfileAction::Switch { slot } => return execute_requests(vec![RequestMessage::SetActiveProfile { profile_id: slot }], session),
+                ProfileAction::Export { .. } if session.dry_run => return execute_requests(vec![RequestMessage::GetProfiles], session),
+                ProfileAction::Export { output } => {
+                    let profiles = serde_json::to_string_pretty(&session.device()?.profiles()?)? + "\n";
+                    match output {
+                        Some(path) => std::fs::write(path, profiles)?,
+                        None => print!("{}", profiles),
+                    }
+                    return Ok(());
+                }
+                ProfileAction::BindSwitch { button_id, slot } => {
+                    let slots = match slot {
+                        Some(slot) => vec![slot],
+                        None => (0..=MAX_PROFILE_SLOT).collect(),
+                    };
+                    let requests = slots
+                        .into_iter()
+                        .map(|profile_id| {
+                            Ok(RequestMessage::SetButton { profile_id, button: profile_switch_button(button_id)?, is_js_keycode: false })
+                        })
+                        .collect::<Result<_, AzeronCliError>>()?;
+                    return execute_requests(requests, session);
+                }
+                ProfileAction::Apply { path, buttons, slot } => {
+                    if slot > MAX_PROFILE_SLOT {
+                        return Err(AzeronCliError::ValueOutOfRange { name: "profile slot".to_string(), value: slot as i64, min: 0, max: MAX_PROFILE_SLOT as i64 });
+                    }
+                    let profile = read_profile_file(&path)?;
+                    let buttons = match buttons {
+                        Some(ButtonIds(button_ids)) => profile.select_buttons(&button_ids)?,
+                        None => profile.buttons,
+                    };
+                    let requests = buttons
+                        .into_iter()
+                        .map(|button| RequestMessage::SetButton { profile_id: slot, button, is_js_keycode: true })
+                        .collect();
+                    return execute_requests(requests, session);
+                }
+                ProfileAction::Load { path } => (read_profile_file(&path)?, None),
+                ProfileAction::Activate { name, slot } => (store.load(&name)?, slot),
+                action => return profile_library(&store, action),
+            };
+            match slot {
+                Some(slot) if slot > MAX_PROFILE_SLOT => {
+                    return Err(AzeronCliError::ValueOutOfRange { name: "profile slot".to_string(), value: slot as i64, min: 0, max: MAX_PROFILE_SLOT as i64 });
+                }
+                None if !profile.is_software => return Err(AzeronCliError::NotSoftwareProfile { name: profile.name }),
+                None if !session.dry_run => {
+                    session.device()?.load_software_profile(&profile)?;
+                    println!("Loaded {}.", profile.name);
+                    return Ok(());
+                }
+                _ => {}
+            }
+            let slot = slot.unwrap_or(SOFTWARE_PROFILE_SLOT);
+            profile
+                .buttons
+                .into_iter()
+                .map(|button| RequestMessage::SetButton { profile_id: slot, button, is_js_keycode: true })
+                .collect()
+        }
+        CliAction::Raw { command, .. } if session.dry_run => vec![RequestMessage::Custom(command)],
+        CliAction::Raw { command, replies, format } => {
+            let timeout = session.options.timeout;
+            let reports = match session.device()? {
+                Device::Direct(azeron) => RequestMessage::Custom(command).collect_reports(azeron.transport(), replies, timeout)?,
+                #[cfg(unix)]
+                Device::Daemon(client) => client.collect_reports(&command, replies, timeout)?,
+            };
+            if reports.is_empty() {
+                return Err(AzeronCliError::Timeout);
+            }
+            for (index, report) in reports.iter().enumerate() {
+                print_report(index, report, format);
+            }
+            return Ok(());
+        }
+        #[cfg(unix)]
+        CliAction::Focus { application } => {
+            let socket = session.socket.display().to_string();
+            let client = DaemonClient::connect(&session.socket).map_err(|_| AzeronCliError::DaemonNotRunning { path: socket })?;
+            return client.set_focus(application);
+        }
+        CliAction::Status if session.dry_run => vec![RequestMessage::GetFirmwareVersion, RequestMessage::GetActiveProfile],
+        CliAction::Status => return status(session),
+        CliAction::Devices | CliAction::Doctor { .. } | CliAction::Run { .. } => return Err(AzeronCliError::UnsupportedInScript),
+        #[cfg(unix)]
+        CliAction::Daemon { .. } => return Err(AzeronCliError::UnsupportedInScript),
+    };
+
+    execute_requests(requests, session)
+}
+
+/// Sends requests one after another, or prints their frames with `--dry-run`.
+fn execute_requests(requests: Vec<RequestMessage>, session: &mut Session) -> Result<(), AzeronCliError> {
+    if session.dry_run {
+        for request in &requests {
+            print_frame(&request.frame()?);
         }
+        return Ok(());
     }
+
+    for request in &requests {
+        request.validate()?;
+    }
+
+    let device = session.device()?;
+    for request in requests {
+        let response = device.send(&request)?;
+        println!("success: {}", response);
+    }
+
+    Ok(())
+}
+
+/// Runs every command of a script over a single session and prints a summary.
+///
+/// The whole script is parsed before anything is sent, so a typo doesn't leave the keypad half-configured.
+fn run_script(path: &Path, keep_going: bool, session: &mut Session) -> Result<(), AzeronCliError> {
+    let lines = read_script(path)?;
+    let actions = lines
+        .iter()
+        .map(|line| {
+            let invalid = |message: String| AzeronCliError::InvalidScriptLine { line: line.number, message };
+            match ScriptArgs::try_parse_from(&line.words) {
+                Ok(args) if !runs_in_script(&args.subcommand) => Err(invalid(AzeronCliError::UnsupportedInScript.to_string())),
+                Ok(args) => Ok(args.subcommand),
+                Err(e) => Err(invalid(e.to_string().trim_start_matches("error: ").trim_end().to_string())),
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut succeeded = 0;
+    let mut first_error = None;
+    for (line, action) in lines.iter().zip(actions) {
+        println!("> {}", line.text);
+        match execute(action, session) {
+            Ok(()) => succeeded += 1,
+            Err(e) => {
+                eprintln!("error on line {}: {}", line.number, e);
+                first_error.get_or_insert(e);
+                if !keep_going {
+                    break;
+                }
+            }
+        }
+    }
+
+    let failed = if first_error.is_some() { lines.len() - succeeded } else { 0 };
+    println!("{} of {} commands succeeded, {} failed or skipped", succeeded, lines.len(), failed);
+
+    match first_error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// Commands that open the device or a socket of their own can't be part of a script.
+fn runs_in_script(action: &CliAction) -> bool {
+    match action {
+        CliAction::Devices | CliAction::Doctor { .. } | CliAction::Run { .. } => false,
+        #[cfg(unix)]
+        CliAction::Daemon { .. } => false,
+        _ => true,
+    }
+}
+
+fn status(session: &mut Session) -> Result<(), AzeronCliError> {
+    let device = session.device()?;
+    let query = |request: RequestMessage| match device.send(&request)? {
+        ResponseMessage::Reply(reply) => Ok(reply),
+        response => Err(AzeronCliError::MalformedResponse { response: response.to_string() }),
+    };
+
+    match query(RequestMessage::GetFirmwareVersion)? {
+        Reply::FirmwareVersion(version) => println!("firmware:         {}", version),
+        reply => return Err(AzeronCliError::MalformedResponse { response: reply.to_string() }),
+    }
+    let active_profile = match query(RequestMessage::GetActiveProfile)? {
+        Reply::ActiveProfile(profile_id) => profile_id,
+        reply => return Err(AzeronCliError::MalformedResponse { response: reply.to_string() }),
+    };
+    println!("active profile:   slot {}", active_profile);
+
+    #[cfg(unix)]
+    if let Device::Daemon(client) = device {
+        let status = client.status(active_profile)?;
+        match status.software_profile {
+            Some(profile) => println!(
+                "software profile: {} in slot {}{}",
+                profile.name,
+                status.software_slot,
+                if profile.is_active { " (active)" } else { "" }
+            ),
+            None => println!("software profile: none loaded (slot {})", status.software_slot),
+        }
+    }
+
+    Ok(())
+}
+
+/// Handles the profile commands that only touch the library, not the device.
+fn profile_library(store: &ProfileStore, action: ProfileAction) -> Result<(), AzeronCliError> {
+    match action {
+        ProfileAction::List => {
+            for profile in store.list()? {
+                let kind = if profile.is_software { "software" } else { "hardware" };
+                let favourite = if profile.is_favourite { "*" } else { " " };
+                println!("{} {} ({})", favourite, profile.name, kind);
+            }
+        }
+        ProfileAction::Show { name, diagram: true } => println!("{}", render_diagram(&store.load(&name)?)),
+        ProfileAction::Show { name, diagram: false } => {
+            let profile = store.load(&name)?;
+            println!("name:      {}", profile.name);
+            println!("id:        {}", profile.id);
+            println!("software:  {}", if profile.is_software { "yes" } else { "no" });
+            match &profile.favourited_at {
+                Some(since) if profile.is_favourite => println!("favourite: yes (since {})", since),
+                _ => println!("favourite: {}", if profile.is_favourite { "yes" } else { "no" }),
+            }
+            println!("hash:      {}", profile.hash);
+            println!("buttons:");
+            for button in &profile.buttons {
+                let join = |values: &[StringOrU32]| values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(",");
+                println!(
+                    "  {:>2}  {:<36} keys: {:<12} meta: {:<12} {}",
+                    button.id,
+                    format!("{:?}", button.button_type),
+                    join(&button.key_values),
+                    join(&button.meta_keys),
+                    button.user_label
+                );
+            }
+        }
+        ProfileAction::Import { path, name } => {
+            let profile = read_profile_file(&path)?;
+            let name = name.unwrap_or_else(|| profile.name.clone());
+            store.import(profile, &name)?;
+            println!("Imported {} into {}.", name, store.dir().display());
+        }
+        ProfileAction::New { name, template, output } => {
+            let profile = match template {
+                Some(template) => Template::find(&template)?.instantiate(&name)?,
+                None => Profile::new(0, &name),
+            };
+            match output {
+                Some(path) => write_profile_file(&path, &profile)?,
+                None => {
+                    store.import(profile, &name)?;
+                    println!("Created {} in {}.", name, store.dir().display());
+                }
+            }
+        }
+        ProfileAction::Templates => {
+            for template in TEMPLATES {
+                println!("{:<8} {}", template.name, template.description);
+            }
+        }
+        ProfileAction::Convert { input, output } => write_profile_file(&output, &read_profile_file(&input)?)?,
+        ProfileAction::Merge { base, overlay, buttons, output } => {
+            let mut profile = read_profile_file(&base)?;
+            let overlay = read_profile_file(&overlay)?;
+            profile.merge(match buttons {
+                Some(ButtonIds(button_ids)) => overlay.select_buttons(&button_ids)?,
+                None => overlay.buttons,
+            });
+            match output {
+                Some(path) => write_profile_file(&path, &profile)?,
+                None => println!("{}", ProfileFormat::Json.write(&profile)?),
+            }
+        }
+        ProfileAction::Mirror { input, output } => {
+            let mut profile = read_profile_file(&input)?;
+            profile.mirror();
+            write_profile_file(&output, &profile)?;
+        }
+        ProfileAction::Check { path } => {
+            let profile = read_profile_file(&path)?;
+            let problems = check_profile(&profile);
+            for problem in &problems {
+                println!("{}", problem);
+            }
+            if !problems.is_empty() {
+                return Err(AzeronCliError::ProfileCheckFailed { name: path.display().to_string(), count: problems.len() });
+            }
+            println!("No problems found in {}.", path.display());
+        }
+        ProfileAction::Rename { name, new_name } => store.rename(&name, &new_name)?,
+        ProfileAction::Delete { name } => store.delete(&name)?,
+        ProfileAction::Favourite { name } => store.set_favourite(&name, true)?,
+        ProfileAction::Unfavourite { name } => store.set_favourite(&name, false)?,
+        ProfileAction::Load { .. }
+        | ProfileAction::Activate { .. }
+        | ProfileAction::Apply { .. }
+        | ProfileAction::Switch { .. }
+        | ProfileAction::Export { .. }
+        | ProfileAction::BindSwitch { .. } => {
+            unreachable!("handled by execute")
+        }
+    }
+
+    Ok(())
+}
+
+/// Holds the device open and serves requests on the socket until SIGTERM or SIGINT.
+#[cfg(unix)]
+fn daemon(session: Session, options: DaemonOptions) -> Result<(), AzeronCliError> {
+    let listener = bind(&session.socket)?;
+    let result = (|| {
+        let transport = open_transport(session.selector.as_deref(), session.replay, session.trace)?;
+        let azeron = Azeron::new(transport).with_options(session.options);
+        azeron.firmware_version()?.ensure_supported()?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        signal_hook::flag::register(signal_hook::consts::SIGTERM, stop.clone())?;
+        signal_hook::flag::register(signal_hook::consts::SIGINT, stop.clone())?;
+
+        println!("Listening on {}.", session.socket.display());
+        serve(azeron.into_transport(), listener, options, &stop)
+    })();
+    // a socket that can't be removed is replaced by the next daemon, it shouldn't hide why this one stopped
+    if let Err(e) = std::fs::remove_file(&session.socket) {
+        eprintln!("warning: couldn't remove {}: {}", session.socket.display(), e);
+    }
+
+    result
+}
+
+fn open_transport(selector: Option<&str>, replay: Option<PathBuf>, trace: Option<PathBuf>) -> Result<Box<dyn Transport + Send>, AzeronCliError> {
+    let device: Box<dyn Transport + Send> = match (replay, trace) {
+        (Some(replay), _) => Box::new(ReplayTransport::open(replay)?),
+        (None, Some(trace)) => Box::new(TraceRecorder::create(open_device(&hidapi::HidApi::new()?, selector)?, trace)?),
+        (None, None) => Box::new(open_device(&hidapi::HidApi::new()?, selector)?),
+    };
+
+    Ok(device)
+}
+
+fn print_frame(frame: &[u8]) {
+    // the first byte is the report ID, it's not part of the message
+    let ascii = frame[1..].iter().flat_map(|b| std::ascii::escape_default(*b)).map(char::from).collect::<String>();
+    let hex = frame.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(" ");
+    println!("ascii: {}", ascii);
+    println!("hex:   {}", hex);
+}
+
+fn print_report(index: usize, report: &[u8], format: RawFormat) {
+    // the zero padding at the end of a report is just noise
+    let end = report.iter().rposition(|b| *b != 0).map_or(0, |i| i + 1);
+    let report = &report[..end];
+
+    println!("reply {}:", index + 1);
+    if format != RawFormat::Hex {
+        let text = report
+            .iter()
+            .map(|b| if b.is_ascii_graphic() || *b == b' ' { *b as char } else { '.' })
+            .collect::<String>();
+        println!("  text: {}", text);
+    }
+    if format != RawFormat::Text {
+        let hex = report.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(" ");
+        println!("  hex:  {}", hex);
+    }
+}
+
+fn doctor(print_udev_rule: bool, install_udev_rule: bool) -> Result<(), AzeronCliError> {
+    #[cfg(target_os = "linux")]
+    {
+        use azeron::doctor::{UDEV_RULE, UDEV_RULE_PATH};
+        if print_udev_rule {
+            print!("{}", UDEV_RULE);
+            return Ok(());
+        }
+        if install_udev_rule {
+            azeron::doctor::install_udev_rule(std::path::Path::new(UDEV_RULE_PATH))?;
+            println!("Installed udev rule to {}.", UDEV_RULE_PATH);
+            println!("Reload the rules with `sudo udevadm control --reload-rules && sudo udevadm trigger`, then replug the keypad.");
+            return Ok(());
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    if print_udev_rule || install_udev_rule {
+        println!("udev rules are only used on Linux.");
+        return Ok(());
+    }
+
+    let api = hidapi::HidApi::new()?;
+    let found = azeron_devices(&api).count();
+    if found == 0 {
+        println!("[problem] No Azeron keypad found. Make sure it is plugged in and shows up as 16d0:10bc in `lsusb`.");
+    } else {
+        println!("[ok] Found {} Azeron keypad(s).", found);
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        use azeron::doctor::{format_mode, hidraw_nodes, UDEV_RULE_PATH};
+        let nodes = hidraw_nodes(std::path::Path::new("/sys/class/hidraw"), std::path::Path::new("/dev"))?;
+        let mut denied = None;
+        for node in &nodes {
+            let interface = node.interface.map_or("unknown".to_string(), |i| i.to_string());
+            let status = match node.access {
+                Ok(()) => "[ok]",
+                Err(_) => "[problem]",
+            };
+            println!(
+                "{} {} (interface {}): {} owned by {}:{}",
+                status,
+                node.path.display(),
+                interface,
+                format_mode(node.mode),
+                node.uid,
+                node.gid
+            );
+            if node.access.is_err() && denied.is_none() {
+                denied = Some(node.path.display().to_string());
+            }
+        }
+
+        if let Some(path) = denied {
+            println!();
+            println!("Your user can't open the keypad's hidraw nodes, which is needed to configure it.");
+            println!("Install a udev rule that gives the logged in user access:");
+            println!();
+            println!("    sudo azeron-cli doctor --install-udev-rule");
+            println!();
+            println!("or write the output of `azeron-cli doctor --print-udev-rule` to {} yourself,", UDEV_RULE_PATH);
+            println!("then run `sudo udevadm control --reload-rules && sudo udevadm trigger` and replug the keypad.");
+            return Err(AzeronCliError::PermissionDenied { path });
+        }
+        if found > 0 && !nodes.is_empty() {
+            println!("[ok] All hidraw nodes of the keypad can be opened.");
+        }
+    }
+
+    if found == 0 {
+        return Err(AzeronCliError::DeviceNotFound);
+    }
+
+    Ok(())
 }
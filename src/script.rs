@@ -0,0 +1,77 @@
+use std::{
+    fs,
+    io::{self, Read},
+    path::Path,
+};
+
+use azeron_cli::AzeronCliError;
+
+/// A single command of a script, split into words like a shell would.
+pub struct ScriptLine {
+    pub number: usize,
+    pub text: String,
+    pub words: Vec<String>,
+}
+
+/// Reads a script from a file, or from stdin if the path is `-`.
+///
+/// Every line is a command as it would be given to `azeron-cli`, without the binary name.
+/// Empty lines and lines starting with `#` are skipped.
+pub fn read_script(path: &Path) -> Result<Vec<ScriptLine>, AzeronCliError> {
+    let source = if path == Path::new("-") {
+        let mut source = String::new();
+        io::stdin().read_to_string(&mut source)?;
+        source
+    } else {
+        fs::read_to_string(path)?
+    };
+
+    source
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|(index, line)| {
+            let words = split_words(line).map_err(|message| AzeronCliError::InvalidScriptLine { line: index + 1, message })?;
+            Ok(ScriptLine { number: index + 1, text: line.trim().to_string(), words })
+        })
+        .collect()
+}
+
+/// Splits a line at whitespace, keeping text in single or double quotes together.
+fn split_words(line: &str) -> Result<Vec<String>, String> {
+    let mut words = vec![];
+    let mut word: Option<String> = None;
+    let mut quote = None;
+
+    for c in line.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err("unterminated quote".to_string());
+    }
+    words.extend(word);
+
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::split_words;
+
+    #[test]
+    fn splits_words_and_quotes() {
+        assert_eq!(split_words("set-button -m CTRL 1 10 61459").unwrap(), ["set-button", "-m", "CTRL", "1", "10", "61459"]);
+        assert_eq!(split_words(r#"raw "ST|21"  -n 2"#).unwrap(), ["raw", "ST|21", "-n", "2"]);
+        assert_eq!(split_words("raw ''").unwrap(), ["raw", ""]);
+        assert!(split_words("raw \"GET_LEDS").is_err());
+    }
+}